@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use crate::graph::router::area::Area;
+use crate::graph::router::isochrone::Contour;
+use crate::osm::Coordinates;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+pub struct FeatureCollection {
+    features: Vec<Feature>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+struct Feature {
+    geometry: Geometry,
+    properties: Properties,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", content = "coordinates")]
+enum Geometry {
+    MultiPolygon(Vec<Vec<Vec<[f64; 2]>>>),
+    MultiLineString(Vec<Vec<[f64; 2]>>),
+}
+
+/// Polygon geometry given as input, the first ring of a polygon is its outline
+/// and the others are holes
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "coordinates")]
+pub enum Polygons {
+    Polygon(Vec<Vec<[f64; 2]>>),
+    MultiPolygon(Vec<Vec<Vec<[f64; 2]>>>),
+}
+
+impl Polygons {
+    pub fn areas(&self) -> Vec<Area> {
+        let area = |rings: &Vec<Vec<[f64; 2]>>| Area::new(rings.iter()
+            .map(|ring| ring.iter().map(|[lon, lat]| Coordinates::from(*lat, *lon)).collect())
+            .collect());
+        match self {
+            Polygons::Polygon(rings) => vec![area(rings)],
+            Polygons::MultiPolygon(polygons) => polygons.iter().map(area).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Properties {
+    budget: u32,
+    /// Either the reachable "area" or the reachable "edges"
+    kind: &'static str,
+}
+
+/// Area and edges of every contour as separate features
+pub fn isochrones(contours: &[Contour]) -> FeatureCollection {
+    let mut features = Vec::with_capacity(contours.len() * 2);
+    for contour in contours {
+        let polygons = contour.area.iter()
+            .map(|ring| vec![ring.iter().map(position).collect()])
+            .collect();
+        features.push(Feature {
+            geometry: Geometry::MultiPolygon(polygons),
+            properties: Properties { budget: contour.budget, kind: "area" },
+        });
+
+        let lines = contour.edges.iter()
+            .map(|line| line.iter().map(position).collect())
+            .collect();
+        features.push(Feature {
+            geometry: Geometry::MultiLineString(lines),
+            properties: Properties { budget: contour.budget, kind: "edges" },
+        });
+    }
+    FeatureCollection {
+        features,
+    }
+}
+
+/// GeoJSON positions are longitude first
+fn position(coordinates: &Coordinates) -> [f64; 2] {
+    [coordinates.lon(), coordinates.lat()]
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::geojson::{isochrones, Polygons};
+    use crate::graph::router::isochrone::Contour;
+    use crate::osm::Coordinates;
+
+    #[test]
+    fn feature_collection() {
+        let a = Coordinates::from(48.0, 9.0);
+        let b = Coordinates::from(48.0, 9.5);
+        let c = Coordinates::from(48.5, 9.5);
+        let contour = Contour {
+            budget: 600,
+            area: vec![vec![a.clone(), b.clone(), c, a.clone()]],
+            edges: vec![[a, b]],
+        };
+
+        let value = serde_json::to_value(isochrones(&[contour])).unwrap();
+        assert_eq!(value["type"], "FeatureCollection");
+        assert_eq!(value["features"][0], json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "MultiPolygon",
+                "coordinates": [[[[9.0, 48.0], [9.5, 48.0], [9.5, 48.5], [9.0, 48.0]]]],
+            },
+            "properties": { "budget": 600, "kind": "area" },
+        }));
+        assert_eq!(value["features"][1]["geometry"]["type"], "MultiLineString");
+        assert_eq!(value["features"][1]["geometry"]["coordinates"], json!([[[9.0, 48.0], [9.5, 48.0]]]));
+    }
+
+    #[test]
+    fn polygons() {
+        let polygons: Polygons = serde_json::from_value(json!({
+            "type": "MultiPolygon",
+            "coordinates": [
+                [[[9.0, 48.0], [9.1, 48.0], [9.1, 48.1], [9.0, 48.0]]],
+                [[[9.5, 48.5], [9.6, 48.5], [9.6, 48.6], [9.5, 48.5]]],
+            ],
+        })).unwrap();
+        let areas = polygons.areas();
+        assert_eq!(areas.len(), 2);
+        // longitude first, so the segment along 48.05 crosses the first polygon
+        let (from, to) = (Coordinates::from(48.05, 8.9), Coordinates::from(48.05, 9.2));
+        assert!(areas[0].intersects(&from, &to));
+        assert!(!areas[1].intersects(&from, &to));
+    }
+}
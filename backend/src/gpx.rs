@@ -0,0 +1,148 @@
+use std::fmt::Write;
+
+use crate::graph::router::route::Route;
+use crate::osm::Coordinates;
+
+/// Track points used at most for re-routing a GPX file without waypoints
+const MAX_TRACK_STOPS: usize = 25;
+
+/// GPX 1.1 with the stops as waypoints, the instructions as route and the nodes as track
+pub fn export(route: &Route, stops: &[Coordinates]) -> String {
+    let mut gpx = String::new();
+    gpx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    gpx.push_str("<gpx version=\"1.1\" creator=\"maps\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n");
+
+    for (i, stop) in stops.iter().enumerate() {
+        writeln!(gpx, "  <wpt {}>", position(stop)).unwrap();
+        writeln!(gpx, "    <name>Stop {}</name>", i + 1).unwrap();
+        gpx.push_str("  </wpt>\n");
+    }
+
+    gpx.push_str("  <rte>\n");
+    for instruction in &route.instructions {
+        let node = &route.nodes[instruction.node];
+        writeln!(gpx, "    <rtept {}>", position(&node.coordinates)).unwrap();
+        writeln!(gpx, "      <name>{}</name>", escape(&instruction.text)).unwrap();
+        gpx.push_str("    </rtept>\n");
+    }
+    gpx.push_str("  </rte>\n");
+
+    gpx.push_str("  <trk>\n    <trkseg>\n");
+    for node in &route.nodes {
+        if let Some(elevation) = node.meta.elevation {
+            writeln!(gpx, "      <trkpt {}><ele>{:.1}</ele></trkpt>",
+                     position(&node.coordinates), elevation).unwrap();
+        } else {
+            writeln!(gpx, "      <trkpt {}/>", position(&node.coordinates)).unwrap();
+        }
+    }
+    gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+    gpx
+}
+
+/// Stops to route through, i.e. the waypoints, otherwise the route points
+/// and as last resort evenly spaced track points
+pub fn import(gpx: &str) -> Result<Vec<Coordinates>, &'static str> {
+    let document = parse(gpx)?;
+    let waypoints = points(&document, "wpt")?;
+    if waypoints.len() >= 2 {
+        return Ok(waypoints);
+    }
+    let route_points = points(&document, "rtept")?;
+    if route_points.len() >= 2 {
+        return Ok(route_points);
+    }
+    let track_points = track_points(&document)?;
+    let step = (track_points.len() - 1) as f64 / (MAX_TRACK_STOPS - 1) as f64;
+    let mut stops: Vec<Coordinates> = (0..MAX_TRACK_STOPS.min(track_points.len()))
+        .map(|i| track_points[(i as f64 * step.max(1.0)).round() as usize].clone())
+        .collect();
+    stops.dedup_by(|a, b| a.lat == b.lat && a.lon == b.lon);
+    Ok(stops)
+}
+
+/// All points of the recorded tracks
+pub fn track(gpx: &str) -> Result<Vec<Coordinates>, &'static str> {
+    track_points(&parse(gpx)?)
+}
+
+fn parse(gpx: &str) -> Result<roxmltree::Document<'_>, &'static str> {
+    roxmltree::Document::parse(gpx)
+        .map_err(|_| "Invalid GPX file")
+}
+
+fn track_points(document: &roxmltree::Document) -> Result<Vec<Coordinates>, &'static str> {
+    let track_points = points(document, "trkpt")?;
+    if track_points.len() < 2 {
+        return Err("GPX file contains less than two points");
+    }
+    Ok(track_points)
+}
+
+fn points(document: &roxmltree::Document, name: &str) -> Result<Vec<Coordinates>, &'static str> {
+    document.descendants()
+        .filter(|n| n.tag_name().name() == name)
+        .map(|n| {
+            let lat = n.attribute("lat").and_then(|l| l.parse().ok());
+            let lon = n.attribute("lon").and_then(|l| l.parse().ok());
+            match (lat, lon) {
+                (Some(lat), Some(lon)) => Ok(Coordinates::from(lat, lon)),
+                _ => Err("GPX point without valid coordinates"),
+            }
+        })
+        .collect()
+}
+
+fn position(coordinates: &Coordinates) -> String {
+    format!("lat=\"{:.7}\" lon=\"{:.7}\"", coordinates.lat(), coordinates.lon())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gpx::{export, import};
+    use crate::graph::fixtures;
+    use crate::graph::router;
+    use crate::graph::router::options::{Params, Routing, Transport};
+
+    #[test]
+    fn export_and_import() {
+        let graph = fixtures::grid(5);
+        let params = Params::new(Transport::Car, Routing::Distance, false);
+        let stops = vec![graph.coordinates(0).clone(), graph.coordinates(24).clone()];
+        let route = router::shortest_path(&graph, &params, &stops[0], &stops[1]).unwrap();
+
+        let gpx = export(&route, &stops);
+        assert_eq!(gpx.matches("<wpt ").count(), 2);
+        assert_eq!(gpx.matches("<rtept ").count(), route.instructions.len());
+        assert_eq!(gpx.matches("<trkpt ").count(), route.nodes.len());
+
+        let imported = import(&gpx).unwrap();
+        assert_eq!(imported.len(), 2);
+        assert_eq!((imported[0].lat, imported[0].lon), (stops[0].lat, stops[0].lon));
+        assert_eq!((imported[1].lat, imported[1].lon), (stops[1].lat, stops[1].lon));
+    }
+
+    #[test]
+    fn import_track() {
+        let mut gpx = String::from("<gpx version=\"1.1\"><trk><trkseg>");
+        for i in 0..100 {
+            gpx.push_str(&format!("<trkpt lat=\"48.{:03}\" lon=\"9.0\"><ele>300</ele></trkpt>", i));
+        }
+        gpx.push_str("</trkseg></trk></gpx>");
+
+        let stops = import(&gpx).unwrap();
+        assert_eq!(stops.len(), 25);
+        assert!((stops[0].lat() - 48.0).abs() < 1e-6);
+        assert!((stops[24].lat() - 48.099).abs() < 1e-6);
+        assert!(import("<gpx></gpx>").is_err());
+        assert!(import("no xml").is_err());
+    }
+}
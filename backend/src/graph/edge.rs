@@ -3,14 +3,25 @@ use std::cmp::Ordering;
 use osmpbfreader::Way;
 use serde::{Deserialize, Serialize};
 
-use crate::graph::router::options::{Params, Routing, Transport};
-use crate::graph::router::options::Routing::Time;
-use crate::graph::router::options::Transport::{Bike, Car, Walk};
-use crate::osm::{Highway, Kmh, Surface};
-use crate::osm::Highway::{Motorway, MotorwayLink, Primary, PrimaryLink, Secondary,
+use crate::graph::router::options::{Avoid, Params, SpeedModel, Transport};
+use crate::graph::speed::{self, INTERSECTION_DELAY};
+use crate::graph::router::options::Routing::{Curvy, Time};
+use crate::graph::router::options::Transport::{All, Bike, BikeWalk, Car, CarBike, Walk};
+use crate::osm::{Highway, Kmh, Oneway, Surface};
+use crate::osm::maxspeed::{self, Country};
+use crate::osm::Highway::{Ferry, Motorway, MotorwayLink, Primary, PrimaryLink, Secondary,
                           SecondaryLink, Tertiary, TertiaryLink, Trunk, TrunkLink};
 use crate::osm::Surface::{Asphalt, Concrete, Paved};
 
+/// Curvature score per kilometer from which on an edge is considered to be fully curvy
+const CURVY_SCORE_PER_KM: f32 = 60.0;
+pub const BIKE_SPEED: u8 = 20;
+pub const WALK_SPEED: u8 = 5;
+/// Grade in tenths of a percent up to which descents are not penalized
+const STEEP_DESCENT: i16 = 60;
+/// Grade in tenths of a percent from which roads are fully preferred by hilly round trips
+const HILLY_GRADE: i16 = 80;
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Edge {
     pub source_index: usize,
@@ -29,20 +40,70 @@ impl Edge {
         }
     }
 
-    pub fn cost(&self, mode: Transport, routing: Routing) -> u32 {
-        if mode == Car && routing == Time {
-            self.meta.max_speed.time(self.distance())
+    pub fn cost(&self, params: &Params) -> u32 {
+        let cost = match params.routing {
+            Time => self.time(params),
+            Curvy => self.curvy_cost(params.curviness),
+            _ => self.distance()
+        };
+        let cost = if params.grade_weight > 0.0 {
+            (cost as f32 * self.grade_factor(params.grade_weight)).round() as u32
+        } else {
+            cost
+        };
+        if params.hilliness > 0.0 {
+            cost + (params.hilliness * cost as f32 * (1.0 - self.steepness())).round() as u32
         } else {
-            // Bike and Walk are assumed to have constant speed
-            self.distance()
+            cost
+        }
+    }
+
+    /// Share of the grade, climbing or descending, of a fully hilly road
+    fn steepness(&self) -> f32 {
+        self.meta.grade
+            .map_or(0.0, |grade| (f32::from(grade.abs()) / f32::from(HILLY_GRADE)).min(1.0))
+    }
+
+    /// Climbs cost `weight` times more per 10% of grade,
+    /// descents only if they are too steep to roll down comfortably
+    fn grade_factor(&self, weight: f32) -> f32 {
+        match self.meta.grade {
+            Some(grade) if grade > 0 => 1.0 + weight * f32::from(grade) / 100.0,
+            Some(grade) if grade < -STEEP_DESCENT =>
+                1.0 + weight * f32::from(-grade - STEEP_DESCENT) / 200.0,
+            _ => 1.0
+        }
+    }
+
+    /// Straight edges cost up to (1 + weight) times their distance,
+    /// curvy ones only their distance
+    fn curvy_cost(&self, weight: f32) -> u32 {
+        let distance = self.distance();
+        if distance == 0 {
+            return 0;
         }
+        let score_per_km = f32::from(self.meta.curvature) / 2.0 / (distance as f32 / 1000.0);
+        let curviness = (score_per_km / CURVY_SCORE_PER_KM).min(1.0);
+        distance + (weight * distance as f32 * (1.0 - curviness)).round() as u32
     }
 
-    pub fn time(&self, mode: Transport) -> u32 {
-        match mode {
-            Car => self.meta.max_speed.time(self.distance()),
-            Bike => Kmh::new(20).time(self.distance()),
-            Walk => Kmh::new(5).time(self.distance()),
+    /// Seconds needed with the transport and speed model of the params
+    pub fn time(&self, params: &Params) -> u32 {
+        let seconds = |speed: f32| (self.distance() as f32 / (speed.max(1.0) / 3.6)).round() as u32;
+        match (params.transport, params.speed_model) {
+            (Car, _) => {
+                // motorway junctions are passed without slowing down
+                let delay = if self.meta.intersection && self.meta.highway != Motorway {
+                    INTERSECTION_DELAY
+                } else {
+                    0
+                };
+                seconds(speed::car(&self.meta)) + delay
+            }
+            (Bike, SpeedModel::Constant) => Kmh::new(BIKE_SPEED).time(self.distance()),
+            (Bike, SpeedModel::Terrain { power, weight }) => seconds(speed::bike(&self.meta, power, weight)),
+            (Walk, SpeedModel::Constant) => Kmh::new(WALK_SPEED).time(self.distance()),
+            (Walk, SpeedModel::Terrain { .. }) => seconds(speed::walk(&self.meta)),
             _ => panic!("Unsupported transport mode")
         }
     }
@@ -52,31 +113,35 @@ impl Edge {
         self.distance.unwrap()
     }
 
-    pub fn transport(&self) -> Transport {
-        Transport::from(self.meta.highway)
+    pub fn allows(&self, transport: Transport) -> bool {
+        self.meta.access.allows(transport)
     }
 
     pub fn is_paved(&self) -> bool {
         match self.meta.highway {
             Motorway | Trunk | Primary | Secondary | Tertiary |
-            MotorwayLink | TrunkLink | PrimaryLink | SecondaryLink | TertiaryLink => return true,
+            MotorwayLink | TrunkLink | PrimaryLink | SecondaryLink | TertiaryLink | Ferry => return true,
             _ => ()
         }
-        if let Some(surface) = self.meta.surface {
-            match surface {
-                Asphalt | Concrete | Paved => return true,
-                _ => ()
-            }
-        }
-        false
+        matches!(self.meta.surface, Some(Asphalt) | Some(Concrete) | Some(Paved))
     }
 
     pub fn is_relevant(&self, params: &Params) -> bool {
-        let matches_transport = self.transport().contains(params.transport);
-        if params.avoid_unpaved {
-            matches_transport && self.is_paved()
-        } else {
-            matches_transport
+        let matches_transport = self.allows(params.transport);
+        let matches_surface = !params.avoid_unpaved || self.is_paved();
+        matches_transport && matches_surface && self.is_climbable(params.max_grade) && !self.is_avoided(params)
+    }
+
+    fn is_avoided(&self, params: &Params) -> bool {
+        params.avoid.contains(&Avoid::Highway(self.meta.highway)) ||
+            (self.meta.toll && params.avoid.contains(&Avoid::Toll))
+    }
+
+    /// Only climbs are limited, descents are always allowed
+    fn is_climbable(&self, max_grade: Option<u8>) -> bool {
+        match (self.meta.grade, max_grade) {
+            (Some(grade), Some(max_grade)) => grade <= i16::from(max_grade) * 10,
+            _ => true
         }
     }
 }
@@ -96,24 +161,245 @@ impl PartialOrd for Edge {
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Meta {
-    pub grade: Option<u8>,
+    /// In tenths of a percent, positive if the edge goes uphill
+    pub grade: Option<i16>,
     pub max_speed: Kmh,
     pub highway: Highway,
     pub surface: Option<Surface>,
+    /// Sum of the curvature scores at both ends of the edge
+    pub curvature: u8,
+    /// Radius in meters of the tightest curve at either end of the edge, if it is curved
+    pub radius: Option<u16>,
+    /// Whether the edge ends at an intersection
+    pub intersection: bool,
+    /// Indices into the names of the graph
+    pub name: Option<u32>,
+    pub reference: Option<u32>,
+    pub roundabout: bool,
+    pub toll: bool,
+    pub access: Access,
 }
 
 impl Meta {
-    pub fn new(way: &Way) -> Result<Self, &str> {
-        let highway = Highway::from(&way)
+    /// The max speed is the one in the direction of the way
+    pub fn new(way: &Way, country: Option<Country>) -> Result<Self, &str> {
+        let highway = Highway::from(way)
             .ok_or("Way is not a highway")?;
-        let max_speed = Kmh::from(&way)
-            .or_else(|| highway.default_speed()).unwrap();
+        let max_speed = maxspeed::max_speed(way, highway, country, true);
 
         Ok(Self {
             grade: None,
             max_speed,
             highway,
             surface: Surface::from(way),
+            curvature: 0,
+            radius: None,
+            intersection: false,
+            name: None,
+            reference: None,
+            roundabout: way.tags.contains("junction", "roundabout"),
+            toll: way.tags.contains("toll", "yes"),
+            access: Access::from(way, highway),
+        })
+    }
+}
+
+/// Transport modes which may use an edge, one bit per mode
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Access(u8);
+
+impl Access {
+    const CAR: u8 = 1;
+    const BIKE: u8 = 2;
+    const WALK: u8 = 4;
+
+    /// Starts with the modes usual for the highway type, access tags override them
+    /// from the most general to the most specific one
+    pub fn from(way: &Way, highway: Highway) -> Self {
+        let mut access = Self::of(Transport::from(highway));
+        let tags: [(&str, u8); 6] = [
+            ("access", Self::CAR | Self::BIKE | Self::WALK),
+            ("vehicle", Self::CAR | Self::BIKE),
+            ("motor_vehicle", Self::CAR),
+            ("motorcar", Self::CAR),
+            ("bicycle", Self::BIKE),
+            ("foot", Self::WALK),
+        ];
+        for (key, modes) in &tags {
+            match way.tags.get(*key).map(|value| value.as_str()) {
+                Some("no") | Some("private") | Some("agricultural") | Some("forestry") => access.0 &= !modes,
+                Some("yes") | Some("designated") | Some("permissive") | Some("destination") => access.0 |= modes,
+                _ => ()
+            }
+        }
+        // motorroads are reserved for fast motor vehicles
+        if way.tags.contains("motorroad", "yes") {
+            access.0 &= Self::CAR;
+        }
+        access
+    }
+
+    fn of(transport: Transport) -> Self {
+        Self(match transport {
+            Car => Self::CAR,
+            Bike => Self::BIKE,
+            Walk => Self::WALK,
+            All => Self::CAR | Self::BIKE | Self::WALK,
+            CarBike => Self::CAR | Self::BIKE,
+            BikeWalk => Self::BIKE | Self::WALK,
         })
     }
+
+    pub fn allows(self, transport: Transport) -> bool {
+        let modes = Self::of(transport).0;
+        self.0 & modes == modes
+    }
+
+    /// Only the modes which may travel the way in the given direction
+    pub fn along(self, oneway: &Oneway, is_forward: bool) -> Self {
+        let directions = [(oneway.vehicle, Self::CAR), (oneway.bike, Self::BIKE), (oneway.foot, Self::WALK)];
+        let mut access = self;
+        for (direction, modes) in &directions {
+            if !direction.allows(is_forward) {
+                access.0 &= !modes;
+            }
+        }
+        access
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::edge::{Access, Edge, Meta};
+    use crate::graph::fixtures;
+    use crate::graph::router::options::{Avoid, Params, Routing, Transport};
+    use crate::graph::router::Router;
+    use crate::osm::{Highway, Oneway};
+    use crate::osm::Highway::{Primary, Residential, Track};
+
+    /// Direct road over a hill from west to east and a flat detour over the north
+    fn route(from: usize, to: usize, max_grade: Option<u8>, grade_weight: f32) -> Vec<i64> {
+        let coordinates = [
+            (48.0, 8.99, 100.0),
+            (48.0, 9.0, 150.0),
+            (48.0, 9.01, 110.0),
+            (48.004, 9.0, 105.0),
+        ];
+        let edges = [
+            (0, 1, "tertiary"),
+            (1, 2, "tertiary"),
+            (0, 3, "tertiary"),
+            (3, 2, "tertiary"),
+        ];
+        let graph = fixtures::hilly_graph(&coordinates, &edges);
+        let mut params = Params::new(Transport::Bike, Routing::Distance, false);
+        params.max_grade = max_grade;
+        params.grade_weight = grade_weight;
+
+        let start = graph.coordinates(from);
+        let goal = graph.coordinates(to);
+        Router::new(&graph, params).shortest_path(start, goal).unwrap()
+            .nodes.iter().map(|n| n.id).collect()
+    }
+
+    #[test]
+    fn grade() {
+        let graph = fixtures::hilly_graph(
+            &[(48.0, 8.99, 100.0), (48.0, 9.0, 150.0)],
+            &[(0, 1, "tertiary")],
+        );
+        assert_eq!(graph.edges(0)[0].meta.grade, Some(67));
+        assert_eq!(graph.edges(1)[0].meta.grade, Some(-67));
+    }
+
+    #[test]
+    fn max_grade() {
+        assert_eq!(route(0, 2, None, 0.0), vec![0, 1, 2]);
+        assert_eq!(route(0, 2, Some(8), 0.0), vec![0, 1, 2]);
+        assert_eq!(route(0, 2, Some(6), 0.0), vec![0, 3, 2]);
+        // the climb from the east is gentler and the steep side is only descended
+        assert_eq!(route(2, 0, Some(6), 0.0), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn grade_weight() {
+        assert_eq!(route(0, 2, None, 0.5), vec![0, 1, 2]);
+        assert_eq!(route(0, 2, None, 1.0), vec![0, 3, 2]);
+    }
+
+    #[test]
+    fn avoid() {
+        let edge = |tags: &[(&str, &str)]| Edge::new(0, 1, Meta::new(&fixtures::way(tags), None).unwrap());
+        let motorway = edge(&[("highway", "motorway")]);
+        let toll = edge(&[("highway", "primary"), ("toll", "yes")]);
+        let ferry = edge(&[("route", "ferry")]);
+        assert_eq!(ferry.meta.highway, Highway::Ferry);
+
+        let mut params = Params::new(Transport::Car, Routing::Time, false);
+        assert!(motorway.is_relevant(&params) && toll.is_relevant(&params) && ferry.is_relevant(&params));
+
+        params.avoid.insert("motorway".parse().unwrap());
+        params.avoid.insert(Avoid::Toll);
+        assert!(!motorway.is_relevant(&params));
+        assert!(!toll.is_relevant(&params));
+        assert!(ferry.is_relevant(&params));
+
+        params.avoid.insert("ferry".parse().unwrap());
+        assert!(!ferry.is_relevant(&params));
+    }
+
+    #[test]
+    fn access() {
+        let modes = |highway, tags: &[(&str, &str)]| {
+            let access = Access::from(&fixtures::way(tags), highway);
+            [Transport::Car, Transport::Bike, Transport::Walk].iter()
+                .map(|t| access.allows(*t))
+                .collect::<Vec<_>>()
+        };
+        // defaults of the highway types
+        assert_eq!(modes(Residential, &[]), vec![true, true, true]);
+        assert_eq!(modes(Primary, &[]), vec![true, true, false]);
+        assert_eq!(modes(Track, &[]), vec![false, true, true]);
+
+        assert_eq!(modes(Residential, &[("access", "private")]), vec![false, false, false]);
+        assert_eq!(modes(Residential, &[("access", "no"), ("foot", "yes")]), vec![false, false, true]);
+        assert_eq!(modes(Residential, &[("motor_vehicle", "no")]), vec![false, true, true]);
+        assert_eq!(modes(Residential, &[("vehicle", "no"), ("bicycle", "designated")]), vec![false, true, true]);
+        assert_eq!(modes(Primary, &[("bicycle", "no")]), vec![true, false, false]);
+        assert_eq!(modes(Primary, &[("foot", "designated")]), vec![true, true, true]);
+        assert_eq!(modes(Track, &[("motor_vehicle", "yes")]), vec![true, true, true]);
+        assert_eq!(modes(Primary, &[("motorroad", "yes")]), vec![true, false, false]);
+        assert_eq!(modes(Residential, &[("foot", "no"), ("access", "unknown")]), vec![true, true, false]);
+    }
+
+    #[test]
+    fn access_along() {
+        let along = |tags: &[(&str, &str)], is_forward| {
+            let way = fixtures::way(tags);
+            let meta = Meta::new(&way, None).unwrap();
+            let access = meta.access.along(&Oneway::from(&way, meta.highway), is_forward);
+            [Transport::Car, Transport::Bike, Transport::Walk].iter()
+                .map(|t| access.allows(*t))
+                .collect::<Vec<_>>()
+        };
+        let contraflow = [("highway", "residential"), ("oneway", "yes"), ("oneway:bicycle", "no")];
+        assert_eq!(along(&contraflow, true), vec![true, true, true]);
+        assert_eq!(along(&contraflow, false), vec![false, true, true]);
+
+        let roundabout = [("highway", "primary"), ("junction", "roundabout")];
+        assert_eq!(along(&roundabout, true), vec![true, true, false]);
+        assert_eq!(along(&roundabout, false), vec![false, false, false]);
+    }
+
+    #[test]
+    fn access_tags_are_relevant() {
+        let meta = Meta::new(&fixtures::way(&[("highway", "tertiary"), ("bicycle", "no")]), None).unwrap();
+        let edge = Edge::new(0, 1, meta);
+        assert!(edge.is_relevant(&Params::new(Transport::Car, Routing::Time, false)));
+        assert!(!edge.is_relevant(&Params::new(Transport::Bike, Routing::Distance, false)));
+    }
 }
@@ -0,0 +1,102 @@
+use osmpbfreader::{Tags, Way, WayId};
+
+use crate::graph::{edge, Graph};
+use crate::graph::edge::Edge;
+use crate::graph::names::Names;
+use crate::graph::node::{self, Node};
+use crate::graph::restriction::Restriction;
+use crate::osm::Coordinates;
+use crate::osm::pbf::create_graph;
+
+const HIGHWAYS: [&str; 5] = ["primary", "secondary", "tertiary", "unclassified", "residential"];
+
+pub fn way(tags: &[(&str, &str)]) -> Way {
+    let tags: Tags = tags.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    Way {
+        id: WayId(0),
+        tags,
+        nodes: Vec::new(),
+    }
+}
+
+/// Graph with nodes at the given coordinates and bidirectional edges of the given highway type
+pub fn graph(coordinates: &[(f64, f64)], edges: &[(usize, usize, &str)]) -> Graph {
+    restricted_graph(coordinates, edges, Vec::new())
+}
+
+pub fn restricted_graph(coordinates: &[(f64, f64)], edges: &[(usize, usize, &str)],
+                        restrictions: Vec<Restriction>) -> Graph {
+    let nodes = coordinates.iter()
+        .map(|(lat, lon)| (*lat, *lon, None))
+        .collect::<Vec<_>>();
+    build(&nodes, edges, restrictions)
+}
+
+/// Graph with nodes at the given coordinates and elevations
+pub fn hilly_graph(coordinates: &[(f64, f64, f32)], edges: &[(usize, usize, &str)]) -> Graph {
+    let nodes = coordinates.iter()
+        .map(|(lat, lon, elevation)| (*lat, *lon, Some(*elevation)))
+        .collect::<Vec<_>>();
+    build(&nodes, edges, Vec::new())
+}
+
+fn build(nodes: &[(f64, f64, Option<f32>)], edges: &[(usize, usize, &str)],
+         restrictions: Vec<Restriction>) -> Graph {
+    let nodes = nodes.iter()
+        .enumerate()
+        .map(|(i, (lat, lon, elevation))| Node::new(
+            i as i64, Coordinates::from(*lat, *lon), node::Meta::new(*elevation)))
+        .collect();
+
+    let mut all = Vec::with_capacity(edges.len() * 2);
+    for (source, target, highway) in edges {
+        let meta = edge::Meta::new(&way(&[("highway", highway)]), None).unwrap();
+        all.push(Edge::new(*source, *target, meta.clone()));
+        all.push(Edge::new(*target, *source, meta));
+    }
+    create_graph(nodes, all, restrictions, Names::new())
+}
+
+/// Slightly distorted `size` x `size` grid with pseudo-random highway types,
+/// so shortest paths are (almost always) unique
+pub fn grid(size: usize) -> Graph {
+    let mut random = Random(42);
+    let mut coordinates = Vec::with_capacity(size * size);
+    for i in 0..size {
+        for j in 0..size {
+            let lat = 48.0 + i as f64 * 0.002 + random.next() * 0.000_5;
+            let lon = 9.0 + j as f64 * 0.004 + random.next() * 0.000_5;
+            coordinates.push((lat, lon));
+        }
+    }
+
+    let mut edges = Vec::new();
+    for i in 0..size {
+        for j in 0..size {
+            let index = i * size + j;
+            if j + 1 < size {
+                edges.push((index, index + 1, random.highway()));
+            }
+            if i + 1 < size {
+                edges.push((index, index + size, random.highway()));
+            }
+        }
+    }
+    graph(&coordinates, &edges)
+}
+
+/// Deterministic linear congruential generator, good enough for test data
+pub struct Random(pub u64);
+
+impl Random {
+    pub fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn highway(&mut self) -> &'static str {
+        HIGHWAYS[(self.next() * HIGHWAYS.len() as f64) as usize]
+    }
+}
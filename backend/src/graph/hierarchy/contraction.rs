@@ -0,0 +1,229 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::graph::Graph;
+use crate::graph::hierarchy::{Hierarchy, Shortcut, Via};
+use crate::graph::router::options::Params;
+
+/// Witness searches are aborted after settling this many nodes,
+/// which only leads to superfluous but still correct shortcuts
+const SETTLE_LIMIT: usize = 500;
+
+pub fn contract(graph: &Graph, params: Params) -> Hierarchy {
+    let mut contraction = Contraction::new(graph, &params);
+    let mut queue = BinaryHeap::with_capacity(graph.nodes.len());
+    for node_index in 0..graph.nodes.len() {
+        let priority = contraction.priority(node_index);
+        queue.push(Reverse((priority, node_index)));
+    }
+
+    while let Some(Reverse((_, node_index))) = queue.pop() {
+        if contraction.contracted[node_index] {
+            continue;
+        }
+        // priorities of remaining nodes are only updated lazily
+        let priority = contraction.priority(node_index);
+        if let Some(Reverse((next, _))) = queue.peek() {
+            if priority > *next {
+                queue.push(Reverse((priority, node_index)));
+                continue;
+            }
+        }
+        contraction.contract(node_index);
+    }
+    contraction.hierarchy(params)
+}
+
+struct Contraction {
+    shortcuts: Vec<Shortcut>,
+    /// Shortcuts between uncontracted nodes
+    outgoing: Vec<Vec<usize>>,
+    incoming: Vec<Vec<usize>>,
+    /// Shortcuts that were incident to a node when it was contracted
+    up: Vec<Vec<usize>>,
+    down: Vec<Vec<usize>>,
+    contracted: Vec<bool>,
+    contracted_neighbors: Vec<i64>,
+
+    witness_cost: Vec<u32>,
+    witness_touched: Vec<usize>,
+}
+
+impl Contraction {
+    fn new(graph: &Graph, params: &Params) -> Self {
+        let len = graph.nodes.len();
+        let mut contraction = Self {
+            shortcuts: Vec::new(),
+            outgoing: vec![Vec::new(); len],
+            incoming: vec![Vec::new(); len],
+            up: vec![Vec::new(); len],
+            down: vec![Vec::new(); len],
+            contracted: vec![false; len],
+            contracted_neighbors: vec![0; len],
+
+            witness_cost: vec![u32::MAX; len],
+            witness_touched: Vec::new(),
+        };
+
+        for edge_index in 0..graph.edges.len() {
+            let edge = graph.edge(edge_index);
+            if !edge.is_relevant(params) || edge.source_index == edge.target_index {
+                continue;
+            }
+            let cost = edge.cost(params);
+            contraction.insert(Shortcut {
+                source_index: edge.source_index,
+                target_index: edge.target_index,
+                cost,
+                via: Via::Edge(edge_index),
+            });
+        }
+        contraction
+    }
+
+    /// Edge difference plus the number of already contracted neighbors
+    fn priority(&mut self, node_index: usize) -> i64 {
+        let added = self.necessary_shortcuts(node_index).len() as i64;
+        let removed = (self.incoming[node_index].len() + self.outgoing[node_index].len()) as i64;
+        added - removed + self.contracted_neighbors[node_index]
+    }
+
+    fn contract(&mut self, node_index: usize) {
+        for (first, second) in self.necessary_shortcuts(node_index) {
+            let first_shortcut = self.shortcuts[first];
+            let second_shortcut = self.shortcuts[second];
+            self.insert(Shortcut {
+                source_index: first_shortcut.source_index,
+                target_index: second_shortcut.target_index,
+                cost: first_shortcut.cost + second_shortcut.cost,
+                via: Via::Shortcuts(first, second),
+            });
+        }
+
+        let outgoing = std::mem::take(&mut self.outgoing[node_index]);
+        for &index in &outgoing {
+            let target_index = self.shortcuts[index].target_index;
+            self.incoming[target_index].retain(|i| *i != index);
+            self.contracted_neighbors[target_index] += 1;
+        }
+        let incoming = std::mem::take(&mut self.incoming[node_index]);
+        for &index in &incoming {
+            let source_index = self.shortcuts[index].source_index;
+            self.outgoing[source_index].retain(|i| *i != index);
+            self.contracted_neighbors[source_index] += 1;
+        }
+        self.up[node_index] = outgoing;
+        self.down[node_index] = incoming;
+        self.contracted[node_index] = true;
+    }
+
+    /// Pairs of incoming and outgoing shortcuts without a witness path bypassing the node
+    fn necessary_shortcuts(&mut self, node_index: usize) -> Vec<(usize, usize)> {
+        let mut necessary = Vec::new();
+
+        for i in 0..self.incoming[node_index].len() {
+            let first = self.incoming[node_index][i];
+            let source_index = self.shortcuts[first].source_index;
+            let max_cost = self.outgoing[node_index].iter()
+                .map(|s| self.shortcuts[*s].cost)
+                .max()
+                .unwrap_or(0) + self.shortcuts[first].cost;
+            self.witness_search(source_index, node_index, max_cost);
+
+            for &second in &self.outgoing[node_index] {
+                let target_index = self.shortcuts[second].target_index;
+                if target_index == source_index {
+                    continue;
+                }
+                let cost = self.shortcuts[first].cost + self.shortcuts[second].cost;
+                if self.witness_cost[target_index] > cost {
+                    necessary.push((first, second));
+                }
+            }
+            self.reset_witness();
+        }
+        necessary
+    }
+
+    /// Dijkstra on the remaining graph, ignoring the node that is about to be contracted
+    fn witness_search(&mut self, source_index: usize, ignore_index: usize, max_cost: u32) {
+        let mut queue = BinaryHeap::new();
+        let mut settled = 0;
+        self.witness_cost[source_index] = 0;
+        self.witness_touched.push(source_index);
+        queue.push(Reverse((0, source_index)));
+
+        while let Some(Reverse((cost, node_index))) = queue.pop() {
+            if cost > self.witness_cost[node_index] {
+                continue;
+            }
+            settled += 1;
+            if cost > max_cost || settled > SETTLE_LIMIT {
+                break;
+            }
+
+            for &index in &self.outgoing[node_index] {
+                let shortcut = &self.shortcuts[index];
+                if shortcut.target_index == ignore_index {
+                    continue;
+                }
+                let next = cost + shortcut.cost;
+                if next < self.witness_cost[shortcut.target_index] {
+                    if self.witness_cost[shortcut.target_index] == u32::MAX {
+                        self.witness_touched.push(shortcut.target_index);
+                    }
+                    self.witness_cost[shortcut.target_index] = next;
+                    queue.push(Reverse((next, shortcut.target_index)));
+                }
+            }
+        }
+    }
+
+    fn reset_witness(&mut self) {
+        for index in self.witness_touched.drain(..) {
+            self.witness_cost[index] = u32::MAX;
+        }
+    }
+
+    /// Adds the shortcut unless there already is a cheaper parallel one
+    fn insert(&mut self, shortcut: Shortcut) {
+        let parallel = self.outgoing[shortcut.source_index].iter()
+            .find(|i| self.shortcuts[**i].target_index == shortcut.target_index)
+            .copied();
+        if let Some(index) = parallel {
+            if shortcut.cost < self.shortcuts[index].cost {
+                self.shortcuts[index] = shortcut;
+            }
+            return;
+        }
+
+        let index = self.shortcuts.len();
+        self.shortcuts.push(shortcut);
+        self.outgoing[shortcut.source_index].push(index);
+        self.incoming[shortcut.target_index].push(index);
+    }
+
+    fn hierarchy(self, params: Params) -> Hierarchy {
+        let (up_offsets, up) = flatten(self.up);
+        let (down_offsets, down) = flatten(self.down);
+        Hierarchy {
+            params,
+            shortcuts: self.shortcuts,
+            up_offsets,
+            up,
+            down_offsets,
+            down,
+        }
+    }
+}
+
+fn flatten(lists: Vec<Vec<usize>>) -> (Vec<usize>, Vec<usize>) {
+    let mut offsets = Vec::with_capacity(lists.len() + 1);
+    offsets.push(0);
+    let mut flat = Vec::new();
+    for list in lists {
+        flat.extend(list);
+        offsets.push(flat.len());
+    }
+    (offsets, flat)
+}
@@ -0,0 +1,121 @@
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::graph::Graph;
+use crate::graph::router::options::{Params, PROFILES};
+
+mod contraction;
+
+impl Graph {
+    /// Only default requests of the preprocessed profiles are answered by a hierarchy,
+    /// all other requests fall back to the plain router
+    pub fn contract(&mut self) {
+        self.hierarchies.clear();
+        for (transport, routing) in PROFILES.iter() {
+            debug!("Contracting graph for {:?} by {:?}...", transport, routing);
+            let params = Params::new(*transport, *routing, false);
+            let hierarchy = contraction::contract(self, params);
+            debug!("Contracted graph with {} shortcuts", hierarchy.shortcuts.len());
+            self.hierarchies.push(hierarchy);
+        }
+    }
+
+    pub fn hierarchy(&self, params: &Params) -> Option<&Hierarchy> {
+        self.hierarchies.iter()
+            .find(|h| h.params == *params)
+    }
+}
+
+/// Contraction hierarchy for one set of routing params
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Hierarchy {
+    pub params: Params,
+    shortcuts: Vec<Shortcut>,
+    /// Shortcuts to nodes of higher rank, grouped by source
+    up_offsets: Vec<usize>,
+    up: Vec<usize>,
+    /// Shortcuts from nodes of higher rank, grouped by target
+    down_offsets: Vec<usize>,
+    down: Vec<usize>,
+}
+
+impl Hierarchy {
+    pub fn shortcut(&self, index: usize) -> &Shortcut {
+        &self.shortcuts[index]
+    }
+
+    pub fn up(&self, node_index: usize) -> &[usize] {
+        &self.up[self.up_offsets[node_index]..self.up_offsets[node_index + 1]]
+    }
+
+    pub fn down(&self, node_index: usize) -> &[usize] {
+        &self.down[self.down_offsets[node_index]..self.down_offsets[node_index + 1]]
+    }
+
+    /// Appends the indices of the original edges the shortcut consists of
+    pub fn unpack(&self, index: usize, edges: &mut Vec<usize>) {
+        let mut stack = vec![index];
+        while let Some(index) = stack.pop() {
+            match self.shortcuts[index].via {
+                Via::Edge(edge_index) => edges.push(edge_index),
+                Via::Shortcuts(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+            }
+        }
+    }
+}
+
+/// Either an original edge or the concatenation of two shortcuts
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Shortcut {
+    pub source_index: usize,
+    pub target_index: usize,
+    pub cost: u32,
+    pub via: Via,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Via {
+    Edge(usize),
+    Shortcuts(usize, usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::fixtures;
+    use crate::graph::router::ch::ChRouter;
+    use crate::graph::router::options::{Params, PROFILES, Routing};
+    use crate::graph::router::route::Route;
+    use crate::graph::router::Router;
+
+    #[test]
+    fn same_route_as_router() {
+        let mut graph = fixtures::grid(12);
+        graph.contract();
+
+        for (transport, routing) in PROFILES.iter() {
+            let params = Params::new(*transport, *routing, false);
+            let hierarchy = graph.hierarchy(&params).unwrap();
+            let cost = |route: &Route| route.edges.iter()
+                .map(|e| e.cost(&params))
+                .sum::<u32>();
+
+            for (start, goal) in &[(0, 143), (5, 130), (70, 12), (143, 1), (30, 31)] {
+                let start = graph.coordinates(*start);
+                let goal = graph.coordinates(*goal);
+                let expected = Router::new(&graph, params.clone())
+                    .shortest_path(start, goal).unwrap();
+                let actual = ChRouter::new(&graph, hierarchy)
+                    .shortest_path(start, goal).unwrap();
+
+                // distances and travel times are rounded, so there may be several shortest routes
+                assert_eq!(cost(&expected), cost(&actual));
+                if *routing == Routing::Distance {
+                    assert_eq!(expected.distance, actual.distance);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::graph::Graph;
+use crate::graph::router::options::{Params, PROFILES};
+
+const NUMBER_LANDMARKS: usize = 8;
+
+impl Graph {
+    pub fn select_landmarks(&mut self) {
+        self.landmarks.clear();
+        for (transport, routing) in PROFILES.iter() {
+            debug!("Selecting landmarks for {:?} by {:?}...", transport, routing);
+            let params = Params::new(*transport, *routing, false);
+            let landmarks = Landmarks::new(self, params);
+            self.landmarks.push(landmarks);
+        }
+    }
+
+    /// Landmarks stay admissible for requests which only remove edges from the profile
+    pub fn landmarks(&self, params: &Params) -> Option<&Landmarks> {
+        self.landmarks.iter()
+            .find(|l| l.params.transport == params.transport && l.params.routing == params.routing &&
+                l.params.speed_model == params.speed_model)
+    }
+}
+
+/// Precomputed costs from and to a few landmarks for the ALT heuristic
+/// (A*, landmarks and triangle inequality)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Landmarks {
+    pub params: Params,
+    nodes: Vec<usize>,
+    /// Cost from each landmark to every node
+    from: Vec<Vec<u32>>,
+    /// Cost from every node to each landmark
+    to: Vec<Vec<u32>>,
+}
+
+impl Landmarks {
+    /// Greedily picks nodes which are farthest away from all landmarks selected so far
+    fn new(graph: &Graph, params: Params) -> Self {
+        let forward = adjacency(graph, &params, false);
+        let backward = adjacency(graph, &params, true);
+        let mut landmarks = Self {
+            params,
+            nodes: Vec::with_capacity(NUMBER_LANDMARKS),
+            from: Vec::with_capacity(NUMBER_LANDMARKS),
+            to: Vec::with_capacity(NUMBER_LANDMARKS),
+        };
+        if graph.nodes.is_empty() {
+            return landmarks;
+        }
+
+        let mut next = farthest(&dijkstra(&forward, 0));
+        while landmarks.nodes.len() < NUMBER_LANDMARKS.min(graph.nodes.len()) {
+            if landmarks.nodes.contains(&next) {
+                break;
+            }
+            let from = dijkstra(&forward, next);
+            let to = dijkstra(&backward, next);
+            landmarks.nodes.push(next);
+            landmarks.from.push(from);
+            landmarks.to.push(to);
+
+            let min_costs: Vec<u32> = (0..graph.nodes.len())
+                .map(|i| landmarks.from.iter().map(|costs| costs[i]).min().unwrap())
+                .collect();
+            next = farthest(&min_costs);
+        }
+        landmarks
+    }
+
+    /// Lower bound for the cost between the nodes using the triangle inequality
+    pub fn lower_bound(&self, from: usize, to: usize) -> u32 {
+        let mut bound = 0;
+        for i in 0..self.nodes.len() {
+            let (landmark_from, landmark_to) = (self.from[i][from], self.from[i][to]);
+            if landmark_from != u32::MAX && landmark_to != u32::MAX {
+                bound = bound.max(landmark_to.saturating_sub(landmark_from));
+            }
+            let (from_landmark, to_landmark) = (self.to[i][from], self.to[i][to]);
+            if from_landmark != u32::MAX && to_landmark != u32::MAX {
+                bound = bound.max(from_landmark.saturating_sub(to_landmark));
+            }
+        }
+        bound
+    }
+}
+
+/// Relevant edges grouped by source, or by target if reversed
+fn adjacency(graph: &Graph, params: &Params, reverse: bool) -> Vec<Vec<(usize, u32)>> {
+    let mut adjacency = vec![Vec::new(); graph.nodes.len()];
+    for edge in graph.edges.iter().filter(|e| e.is_relevant(params)) {
+        let cost = edge.cost(params);
+        if reverse {
+            adjacency[edge.target_index].push((edge.source_index, cost));
+        } else {
+            adjacency[edge.source_index].push((edge.target_index, cost));
+        }
+    }
+    adjacency
+}
+
+fn dijkstra(adjacency: &[Vec<(usize, u32)>], start_index: usize) -> Vec<u32> {
+    let mut costs = vec![u32::MAX; adjacency.len()];
+    let mut queue = BinaryHeap::new();
+    costs[start_index] = 0;
+    queue.push(Reverse((0, start_index)));
+
+    while let Some(Reverse((cost, node_index))) = queue.pop() {
+        if cost > costs[node_index] {
+            continue;
+        }
+        for (target_index, edge_cost) in &adjacency[node_index] {
+            let next = cost + edge_cost;
+            if next < costs[*target_index] {
+                costs[*target_index] = next;
+                queue.push(Reverse((next, *target_index)));
+            }
+        }
+    }
+    costs
+}
+
+fn farthest(costs: &[u32]) -> usize {
+    costs.iter()
+        .enumerate()
+        .filter(|(_, c)| **c != u32::MAX)
+        .max_by_key(|(_, c)| **c)
+        .map_or(0, |(i, _)| i)
+}
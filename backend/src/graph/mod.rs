@@ -1,43 +1,79 @@
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Write};
+use std::ops::Range;
 
 use log::debug;
 use serde::{Deserialize, Serialize};
 
 use crate::graph::edge::Edge;
+use crate::graph::hierarchy::Hierarchy;
+use crate::graph::landmarks::Landmarks;
+use crate::graph::names::Names;
 use crate::graph::node::Node;
-use crate::graph::router::grid;
-use crate::osm::Coordinates;
+use crate::graph::restriction::{Restriction, Restrictions};
+use crate::graph::router::spatial;
+use crate::graph::rtree::RTree;
+use crate::osm::{Coordinates, Kmh};
+use crate::osm::maxspeed::Country;
 use crate::osm::pbf::Pbf;
 
 pub mod node;
 pub mod edge;
+pub mod hierarchy;
+pub mod landmarks;
+pub mod names;
+pub mod restriction;
 pub mod router;
-
-pub type Cells = HashMap<Coordinates, Vec<usize>>;
+pub mod rtree;
+pub mod speed;
+#[cfg(test)]
+pub mod fixtures;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Graph {
     nodes: Vec<Node>,
     offsets: Vec<usize>,
     edges: Vec<Edge>,
-    cells: Cells,
+    /// Number of distinct neighbors of each node over in- and outgoing edges
+    degrees: Vec<u8>,
+    /// Spatial indices of the nodes and of the edge segments
+    node_tree: RTree,
+    edge_tree: RTree,
+    restrictions: Restrictions,
+    names: Names,
+    /// Maximum speed of all edges for an admissible travel time heuristic
+    max_speed: Kmh,
+    hierarchies: Vec<Hierarchy>,
+    landmarks: Vec<Landmarks>,
 }
 
 impl Graph {
-    pub fn new(nodes: Vec<Node>, offsets: Vec<usize>, edges: Vec<Edge>) -> Self {
-        let cells = grid::create(&nodes);
+    pub fn new(nodes: Vec<Node>, offsets: Vec<usize>, edges: Vec<Edge>,
+               restrictions: Vec<Restriction>, names: Names) -> Self {
+        let degrees = degrees(nodes.len(), &edges);
+        let node_tree = spatial::node_tree(&nodes);
+        let edge_tree = spatial::edge_tree(&nodes, &edges);
+        let max_speed = edges.iter()
+            .map(|e| e.meta.max_speed.speed)
+            .max()
+            .unwrap_or(1);
         Self {
             nodes,
             edges,
             offsets,
-            cells,
+            degrees,
+            node_tree,
+            edge_tree,
+            restrictions: restriction::group(restrictions),
+            names,
+            max_speed: Kmh::new(max_speed),
+            hierarchies: Vec::new(),
+            landmarks: Vec::new(),
         }
     }
 
-    pub fn from_pbf(filename: &str) -> Self {
-        Pbf::new(filename).read()
+    pub fn from_pbf(filename: &str, country: Option<Country>) -> Self {
+        Pbf::new(filename, country).read()
     }
 
     pub fn from_bin(filename: &str) -> Self {
@@ -65,9 +101,46 @@ impl Graph {
         &self.node(index).coordinates
     }
 
+    pub fn name(&self, index: u32) -> &str {
+        self.names.get(index)
+    }
+
+    pub fn edge(&self, index: usize) -> &Edge {
+        &self.edges[index]
+    }
+
     pub fn edges(&self, node_index: usize) -> &[Edge] {
-        let start = self.offsets[node_index];
-        let end = self.offsets[node_index + 1];
-        &self.edges[start..end]
+        &self.edges[self.edge_indices(node_index)]
+    }
+
+    pub fn edge_indices(&self, node_index: usize) -> Range<usize> {
+        self.offsets[node_index]..self.offsets[node_index + 1]
+    }
+
+    /// Number of distinct nodes connected to the node, one-way roads leading to it included
+    pub fn degree(&self, node_index: usize) -> usize {
+        self.degrees[node_index] as usize
+    }
+
+    /// Indices of all edges from the source to the target node
+    pub fn edges_between(&self, source_index: usize, target_index: usize) -> impl Iterator<Item=usize> + '_ {
+        self.edge_indices(source_index)
+            .filter(move |i| self.edge(*i).target_index == target_index)
+    }
+}
+
+fn degrees(node_count: usize, edges: &[Edge]) -> Vec<u8> {
+    let mut pairs: Vec<(usize, usize)> = edges.iter()
+        .filter(|e| e.source_index != e.target_index)
+        .map(|e| (e.source_index.min(e.target_index), e.source_index.max(e.target_index)))
+        .collect();
+    pairs.sort_unstable();
+    pairs.dedup();
+
+    let mut degrees = vec![0u8; node_count];
+    for (a, b) in pairs {
+        degrees[a] = degrees[a].saturating_add(1);
+        degrees[b] = degrees[b].saturating_add(1);
     }
+    degrees
 }
@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Deduplicated names and refs of ways, edges only store their index
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Names {
+    names: Vec<String>,
+    /// Only needed while parsing
+    #[serde(skip)]
+    indices: HashMap<String, u32>,
+}
+
+impl Names {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: &str) -> u32 {
+        if let Some(index) = self.indices.get(name) {
+            return *index;
+        }
+        let index = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.indices.insert(name.to_string(), index);
+        index
+    }
+
+    pub fn get(&self, index: u32) -> &str {
+        &self.names[index as usize]
+    }
+}
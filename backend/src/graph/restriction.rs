@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::graph::edge::Edge;
+use crate::graph::Graph;
+use crate::graph::router::options::Transport;
+use crate::osm::restriction::Kind;
+
+/// Turn restrictions grouped by the index of their via node
+pub type Restrictions = HashMap<usize, Vec<Restriction>>;
+
+/// Turn from the from node over the via node to the to node
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Restriction {
+    pub from_index: usize,
+    pub via_index: usize,
+    pub to_index: usize,
+    pub kind: Kind,
+    pub transport: Transport,
+}
+
+pub fn group(restrictions: Vec<Restriction>) -> Restrictions {
+    let mut grouped: Restrictions = HashMap::new();
+    for restriction in restrictions {
+        grouped.entry(restriction.via_index)
+            .or_default()
+            .push(restriction);
+    }
+    grouped
+}
+
+impl Graph {
+    pub fn is_turn_allowed(&self, from_index: usize, via_index: usize, to_index: usize,
+                           transport: Transport) -> bool {
+        let restrictions = if let Some(r) = self.restrictions.get(&via_index) { r } else {
+            return true;
+        };
+
+        let applying = || restrictions.iter()
+            .filter(|r| r.from_index == from_index && r.transport.contains(transport));
+        // forbidden turns win over conflicting mandatory ones
+        if applying().any(|r| r.kind == Kind::No && r.to_index == to_index) {
+            return false;
+        }
+        let mut only = applying().filter(|r| r.kind == Kind::Only).peekable();
+        only.peek().is_none() || only.any(|r| r.to_index == to_index)
+    }
+
+    /// Whether the consecutive edges make no forbidden turns
+    pub fn is_path_allowed(&self, path: &[Edge], transport: Transport) -> bool {
+        path.windows(2).all(|edges| self.is_turn_allowed(
+            edges[0].source_index,
+            edges[0].target_index,
+            edges[1].target_index,
+            transport,
+        ))
+    }
+}
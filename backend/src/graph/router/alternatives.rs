@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+
+use crate::graph::{Edge, Graph};
+use crate::graph::router::options::Params;
+use crate::graph::router::route::{Route, RouteBuilder};
+use crate::graph::router::Router;
+use crate::osm::Coordinates;
+
+/// Cost factor applied to the edges of every found route
+const PENALTY_FACTOR: f32 = 1.4;
+/// Searches per requested alternative before giving up
+const SEARCHES_PER_ALTERNATIVE: usize = 4;
+
+/// Up to `count` alternatives to the best route with the penalty method, i.e. the edges of
+/// found routes get more expensive and the search is repeated.
+/// Alternatives share at most `max_overlap` of their distance with the best route
+/// and with each other.
+pub fn alternatives(graph: &Graph, params: &Params, start: &Coordinates, goal: &Coordinates,
+                    best: &Route, count: usize, max_overlap: f32) -> Vec<Route> {
+    let mut alternatives: Vec<Route> = Vec::with_capacity(count);
+    let (start, goal) = match (graph.snap(start, params), graph.snap(goal, params)) {
+        (Ok(start), Ok(goal)) => (start, goal),
+        _ => return alternatives,
+    };
+
+    let mut router = Router::new(graph, params.clone());
+    penalize(graph, &mut router, &best.edges);
+    for _ in 0..count * SEARCHES_PER_ALTERNATIVE {
+        if alternatives.len() == count {
+            break;
+        }
+        let path = if let Ok(path) = router.search_snapped(&start, &goal) { path } else {
+            break;
+        };
+        let edges: Vec<Edge> = path.iter().map(|e| (*e).clone()).collect();
+        penalize(graph, &mut router, &edges);
+
+        let is_different = std::iter::once(best)
+            .chain(alternatives.iter())
+            .all(|other| overlap(&edges, &other.edges) <= max_overlap);
+        if is_different {
+            alternatives.push(RouteBuilder::new(graph, params).build_snapped(&path, &start, &goal));
+        }
+    }
+    alternatives
+}
+
+fn penalize(graph: &Graph, router: &mut Router, edges: &[Edge]) {
+    for edge in edges {
+        for index in graph.edges_between(edge.source_index, edge.target_index) {
+            router.penalize(index, PENALTY_FACTOR);
+        }
+    }
+}
+
+/// Share of the distance of the route which is also part of the other route
+fn overlap(route: &[Edge], other: &[Edge]) -> f32 {
+    let other: HashSet<(usize, usize)> = other.iter()
+        .map(|e| (e.source_index, e.target_index))
+        .collect();
+    let total: u32 = route.iter().map(|e| e.distance()).sum();
+    if total == 0 {
+        return 1.0;
+    }
+    let shared: u32 = route.iter()
+        .filter(|e| other.contains(&(e.source_index, e.target_index)))
+        .map(|e| e.distance())
+        .sum();
+    shared as f32 / total as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::fixtures;
+    use crate::graph::router;
+    use crate::graph::router::alternatives::{alternatives, overlap};
+    use crate::graph::router::options::{Params, Routing, Transport};
+
+    #[test]
+    fn different_routes() {
+        let graph = fixtures::grid(12);
+        let params = Params::new(Transport::Car, Routing::Time, false);
+        let start = graph.coordinates(0);
+        let goal = graph.coordinates(143);
+        let best = router::shortest_path(&graph, &params, start, goal).unwrap();
+
+        let routes = alternatives(&graph, &params, start, goal, &best, 3, 0.5);
+        assert_eq!(routes.len(), 3);
+        for (i, route) in routes.iter().enumerate() {
+            assert_eq!(route.nodes.first().unwrap().id, 0);
+            assert_eq!(route.nodes.last().unwrap().id, 143);
+            assert!(route.time >= best.time);
+            assert!(overlap(&route.edges, &best.edges) <= 0.5);
+            for other in &routes[i + 1..] {
+                assert!(overlap(&route.edges, &other.edges) <= 0.5);
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::graph::Graph;
+use crate::graph::rtree::Rect;
+use crate::osm::Coordinates;
+
+/// Polygon which routes have to stay out of. Points inside an odd number of rings are
+/// inside the area, so inner rings are holes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Area {
+    rings: Vec<Vec<Coordinates>>,
+}
+
+impl Area {
+    /// Rings don't have to be closed, the last point is connected to the first one
+    pub fn new(rings: Vec<Vec<Coordinates>>) -> Self {
+        Self {
+            rings,
+        }
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        self.rings.iter()
+            .flatten()
+            .map(|c| Rect::new(c, c))
+            .reduce(|a, b| a.union(&b))
+    }
+
+    fn sides(&self) -> impl Iterator<Item=(&Coordinates, &Coordinates)> {
+        self.rings.iter()
+            .flat_map(|ring| ring.iter().zip(ring.iter().cycle().skip(1)))
+    }
+
+    /// Even-odd rule with a ray towards increasing longitude
+    fn contains(&self, point: &Coordinates) -> bool {
+        self.sides()
+            .filter(|(a, b)| (a.lat > point.lat) != (b.lat > point.lat))
+            .filter(|(a, b)| {
+                // longitude where the side crosses the latitude of the point
+                let share = f64::from(point.lat - a.lat) / f64::from(b.lat - a.lat);
+                f64::from(point.lon) < f64::from(a.lon) + share * f64::from(b.lon - a.lon)
+            })
+            .count() % 2 == 1
+    }
+
+    /// Whether any part of the segment lies inside the area
+    pub fn intersects(&self, from: &Coordinates, to: &Coordinates) -> bool {
+        self.contains(from) || self.contains(to) ||
+            self.sides().any(|(a, b)| crosses(from, to, a, b))
+    }
+}
+
+impl Graph {
+    /// Indices of the edges which intersect any of the areas,
+    /// only edges whose bounding box overlaps the area are checked exactly
+    pub fn edges_in(&self, areas: &[Area]) -> HashSet<usize> {
+        let mut edges = HashSet::new();
+        for area in areas {
+            let bounds = if let Some(bounds) = area.bounds() { bounds } else { continue };
+            edges.extend(self.edge_tree.intersecting(&bounds).into_iter()
+                .filter(|i| {
+                    let edge = self.edge(*i);
+                    area.intersects(self.coordinates(edge.source_index), self.coordinates(edge.target_index))
+                }));
+        }
+        edges
+    }
+}
+
+/// Whether the segments a-b and c-d touch or cross
+fn crosses(a: &Coordinates, b: &Coordinates, c: &Coordinates, d: &Coordinates) -> bool {
+    let (abc, abd) = (orientation(a, b, c), orientation(a, b, d));
+    let (cda, cdb) = (orientation(c, d, a), orientation(c, d, b));
+    if abc == 0 && abd == 0 {
+        // collinear, so they only touch if their extents overlap
+        let overlaps = |p: fn(&Coordinates) -> i32|
+            p(a).min(p(b)) <= p(c).max(p(d)) && p(c).min(p(d)) <= p(a).max(p(b));
+        return overlaps(|c| c.lat) && overlaps(|c| c.lon);
+    }
+    abc.signum() * abd.signum() <= 0 && cda.signum() * cdb.signum() <= 0
+}
+
+/// Positive if c lies left of a-b, negative if right and 0 if on the line
+fn orientation(a: &Coordinates, b: &Coordinates, c: &Coordinates) -> i64 {
+    let (ax, ay) = (i64::from(a.lon), i64::from(a.lat));
+    (i64::from(b.lon) - ax) * (i64::from(c.lat) - ay) - (i64::from(b.lat) - ay) * (i64::from(c.lon) - ax)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::fixtures;
+    use crate::graph::router::area::Area;
+    use crate::graph::router::options::{Params, Routing, Transport};
+    use crate::graph::router::Router;
+    use crate::osm::Coordinates;
+
+    fn square(lat: f64, lon: f64, size: f64) -> Vec<Coordinates> {
+        vec![
+            Coordinates::from(lat, lon),
+            Coordinates::from(lat, lon + size),
+            Coordinates::from(lat + size, lon + size),
+            Coordinates::from(lat + size, lon),
+        ]
+    }
+
+    #[test]
+    fn intersects() {
+        // square with a hole in the middle
+        let area = Area::new(vec![square(48.0, 9.0, 0.3), square(48.1, 9.1, 0.1)]);
+        let point = |lat, lon| Coordinates::from(lat, lon);
+
+        assert!(area.intersects(&point(48.05, 9.05), &point(48.05, 9.05)));
+        assert!(!area.intersects(&point(48.15, 9.15), &point(48.18, 9.12)));
+        assert!(!area.intersects(&point(47.9, 8.9), &point(47.9, 9.5)));
+        // passes through without any point inside
+        assert!(area.intersects(&point(47.9, 9.05), &point(48.5, 9.05)));
+        // leaves the hole
+        assert!(area.intersects(&point(48.15, 9.15), &point(48.15, 9.25)));
+    }
+
+    #[test]
+    fn route_around() {
+        let graph = fixtures::grid(8);
+        let mut params = Params::new(Transport::Car, Routing::Distance, false);
+        let (start, goal) = (graph.coordinates(24), graph.coordinates(31));
+        let direct = Router::new(&graph, params.clone()).shortest_path(start, goal).unwrap();
+
+        // block the middle of the direct route
+        let middle = &direct.nodes[direct.nodes.len() / 2].coordinates;
+        params.avoid_areas = vec![Area::new(vec![square(middle.lat() - 0.0005, middle.lon() - 0.0005, 0.001)])];
+        let blocked = graph.edges_in(&params.avoid_areas);
+        assert!(!blocked.is_empty());
+
+        let detour = Router::new(&graph, params.clone()).shortest_path(start, goal).unwrap();
+        assert!(detour.distance > direct.distance);
+        for edge in &detour.edges {
+            let (from, to) = (graph.coordinates(edge.source_index), graph.coordinates(edge.target_index));
+            assert!(!params.avoid_areas[0].intersects(from, to));
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::graph::{Edge, Graph};
+use crate::graph::hierarchy::Hierarchy;
+use crate::graph::router::route::{Route, RouteBuilder};
+use crate::graph::router::RouterNode;
+use crate::graph::router::snap::{self, Snap};
+use crate::osm::Coordinates;
+
+/// Bidirectional Dijkstra on a contraction hierarchy
+pub struct ChRouter<'a> {
+    graph: &'a Graph,
+    hierarchy: &'a Hierarchy,
+}
+
+impl<'a> ChRouter<'a> {
+    pub fn new(graph: &'a Graph, hierarchy: &'a Hierarchy) -> Self {
+        Self {
+            graph,
+            hierarchy,
+        }
+    }
+
+    pub fn shortest_path(&self, start: &Coordinates, goal: &Coordinates) -> Result<Route, &'static str> {
+        let params = &self.hierarchy.params;
+        let start = self.graph.snap(start, params)?;
+        let goal = self.graph.snap(goal, params)?;
+        if start.coordinates == goal.coordinates {
+            return Err("No path found, start is goal");
+        }
+        let builder = RouteBuilder::new(self.graph, params);
+        if let Some(edge_index) = snap::direct(self.graph, params, &start, &goal) {
+            return Ok(builder.build_snapped(&[self.graph.edge(edge_index)], &start, &goal));
+        }
+
+        // the searches start at the nodes of the edges the points lie on
+        let sources = self.entries(&start, |edge, fraction| (edge.target_index, 1.0 - fraction));
+        let targets = self.entries(&goal, |edge, fraction| (edge.source_index, fraction));
+        let mut forward = Search::new(&sources);
+        let mut backward = Search::new(&targets);
+        let mut best: Option<(u32, usize)> = None;
+        loop {
+            let bound = best.map_or(u32::MAX, |(cost, _)| cost);
+            let forward_min = forward.min().filter(|c| *c < bound);
+            let backward_min = backward.min().filter(|c| *c < bound);
+            let is_forward = match (forward_min, backward_min) {
+                (None, None) => break,
+                (Some(f), Some(b)) => f <= b,
+                (f, _) => f.is_some(),
+            };
+
+            let (search, other) = if is_forward {
+                (&mut forward, &backward)
+            } else {
+                (&mut backward, &forward)
+            };
+            let node = if let Some(node) = search.pop() { node } else { continue };
+            if let Some((cost, _)) = other.settled.get(&node.index) {
+                let total = node.cost + cost;
+                if total < bound {
+                    best = Some((total, node.index));
+                }
+            }
+
+            let shortcuts = if is_forward {
+                self.hierarchy.up(node.index)
+            } else {
+                self.hierarchy.down(node.index)
+            };
+            for &index in shortcuts {
+                let shortcut = self.hierarchy.shortcut(index);
+                let next_index = if is_forward { shortcut.target_index } else { shortcut.source_index };
+                search.relax(next_index, node.cost + shortcut.cost, index);
+            }
+        }
+
+        let (_, meeting_index) = best.ok_or("No path found")?;
+        let mut shortcuts = forward.path(meeting_index, |s| self.hierarchy.shortcut(s).source_index);
+        shortcuts.reverse();
+        shortcuts.extend(backward.path(meeting_index, |s| self.hierarchy.shortcut(s).target_index));
+
+        let mut edge_indices = Vec::new();
+        for index in shortcuts {
+            self.hierarchy.unpack(index, &mut edge_indices);
+        }
+        let first_index = edge_indices.first()
+            .map_or(meeting_index, |i| self.graph.edge(*i).source_index);
+        let last_index = edge_indices.last()
+            .map_or(meeting_index, |i| self.graph.edge(*i).target_index);
+        let path: Vec<_> = entry_edge(&sources, first_index).into_iter()
+            .chain(edge_indices)
+            .chain(entry_edge(&targets, last_index))
+            .map(|i| self.graph.edge(i))
+            .collect();
+        Ok(builder.build_snapped(&path, &start, &goal))
+    }
+
+    /// Nodes a search starts at with their initial cost and the edge leading there,
+    /// `node` maps an edge with the point on it to the node and the share to traverse
+    fn entries<F>(&self, snap: &Snap, node: F) -> Vec<Entry>
+        where F: Fn(&Edge, f64) -> (usize, f64) {
+        let params = &self.hierarchy.params;
+        if let Some(index) = snap.node_index(self.graph) {
+            return vec![Entry { index, cost: 0, edge_index: None }];
+        }
+        snap.edges(self.graph).into_iter()
+            .filter(|(i, _)| self.graph.edge(*i).is_relevant(params))
+            .map(|(i, fraction)| {
+                let edge = self.graph.edge(i);
+                let (index, share) = node(edge, fraction);
+                let cost = snap::partial_cost(edge.cost(params), share);
+                Entry { index, cost, edge_index: Some(i) }
+            })
+            .collect()
+    }
+}
+
+struct Entry {
+    index: usize,
+    cost: u32,
+    edge_index: Option<usize>,
+}
+
+/// Edge between the point and the node the search started at
+fn entry_edge(entries: &[Entry], index: usize) -> Option<usize> {
+    entries.iter()
+        .filter(|e| e.index == index)
+        .min_by_key(|e| e.cost)
+        .and_then(|e| e.edge_index)
+}
+
+/// One direction of the bidirectional search,
+/// hash maps keep the allocation proportional to the small search space
+struct Search {
+    queue: BinaryHeap<RouterNode>,
+    /// Tentative cost and the shortcut the node was reached by
+    settled: HashMap<usize, (u32, Option<usize>)>,
+}
+
+impl Search {
+    fn new(entries: &[Entry]) -> Self {
+        let mut queue = BinaryHeap::new();
+        let mut settled = HashMap::new();
+        for entry in entries {
+            let is_better = settled.get(&entry.index)
+                .is_none_or(|(c, _)| entry.cost < *c);
+            if is_better {
+                queue.push(RouterNode::new(entry.index, entry.cost, 0));
+                settled.insert(entry.index, (entry.cost, None));
+            }
+        }
+        Self {
+            queue,
+            settled,
+        }
+    }
+
+    fn min(&self) -> Option<u32> {
+        self.queue.peek().map(|n| n.cost)
+    }
+
+    /// Pops the next node unless it was already reached more cheaply
+    fn pop(&mut self) -> Option<RouterNode> {
+        let node = self.queue.pop()?;
+        if node.cost > self.settled[&node.index].0 {
+            return None;
+        }
+        Some(node)
+    }
+
+    fn relax(&mut self, index: usize, cost: u32, shortcut: usize) {
+        let is_better = self.settled.get(&index)
+            .is_none_or(|(c, _)| cost < *c);
+        if is_better {
+            self.settled.insert(index, (cost, Some(shortcut)));
+            self.queue.push(RouterNode::new(index, cost, 0));
+        }
+    }
+
+    /// Shortcuts from the meeting node back to where this search started
+    fn path<F>(&self, meeting_index: usize, next: F) -> Vec<usize>
+        where F: Fn(usize) -> usize {
+        let mut path = Vec::new();
+        let mut index = meeting_index;
+        while let Some((_, Some(shortcut))) = self.settled.get(&index) {
+            path.push(*shortcut);
+            index = next(*shortcut);
+        }
+        path
+    }
+}
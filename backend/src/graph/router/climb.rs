@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+
+use crate::graph::node::Node;
+
+/// Descent in meters after which a climb is considered to be over
+const DIP_TOLERANCE: f32 = 10.0;
+/// Climbs have to be steeper than this average grade in percent
+const MIN_GRADE: f32 = 3.0;
+
+/// Continuous climb along a route, short dips don't interrupt it
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Climb {
+    /// Index of the first node in the route
+    pub start: usize,
+    /// Index of the last node in the route
+    pub end: usize,
+    pub distance: u32,
+    pub ascent: f32,
+    /// In percent
+    pub avg_grade: f32,
+    /// Steepest edge of the climb in percent
+    pub max_grade: f32,
+    pub category: Category,
+}
+
+/// Categories as in cycling, from the easiest to the hardest
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Serialize, Deserialize)]
+pub enum Category {
+    #[serde(rename = "4")]
+    Four,
+    #[serde(rename = "3")]
+    Three,
+    #[serde(rename = "2")]
+    Two,
+    #[serde(rename = "1")]
+    One,
+    /// Hors catégorie
+    #[serde(rename = "HC")]
+    Hors,
+}
+
+impl Category {
+    /// Rates the product of distance in meters and average grade in percent,
+    /// climbs below category 4 aren't rated
+    fn from(distance: u32, avg_grade: f32) -> Option<Self> {
+        let score = distance as f32 * avg_grade;
+        if score >= 80_000.0 {
+            Some(Category::Hors)
+        } else if score >= 64_000.0 {
+            Some(Category::One)
+        } else if score >= 32_000.0 {
+            Some(Category::Two)
+        } else if score >= 16_000.0 {
+            Some(Category::Three)
+        } else if score >= 8_000.0 {
+            Some(Category::Four)
+        } else {
+            None
+        }
+    }
+}
+
+/// Finds all categorized climbs along the nodes, nodes without elevation are skipped
+pub fn detect(nodes: &[Node]) -> Vec<Climb> {
+    let elevations: Vec<(usize, f32)> = nodes.iter()
+        .enumerate()
+        .filter_map(|(i, n)| n.meta.elevation.map(|e| (i, e)))
+        .collect();
+    let mut climbs = Vec::new();
+    if elevations.is_empty() {
+        return climbs;
+    }
+
+    let mut start = 0;
+    let mut peak = 0;
+    for i in 1..elevations.len() {
+        let elevation = elevations[i].1;
+        if elevation > elevations[peak].1 {
+            peak = i;
+        } else if elevations[peak].1 - elevation > DIP_TOLERANCE {
+            climbs.extend(climb(nodes, elevations[start], elevations[peak]));
+            start = i;
+            peak = i;
+        }
+        if elevation <= elevations[start].1 {
+            // climbs start at the lowest point
+            start = i;
+            peak = i;
+        }
+    }
+    climbs.extend(climb(nodes, elevations[start], elevations[peak]));
+    climbs
+}
+
+fn climb(nodes: &[Node], (start, low): (usize, f32), (end, high): (usize, f32)) -> Option<Climb> {
+    if end <= start {
+        return None;
+    }
+    let mut distance = 0.0;
+    let mut max_grade: f32 = 0.0;
+    for pair in nodes[start..=end].windows(2) {
+        let run = pair[0].coordinates.distance(&pair[1].coordinates);
+        distance += run;
+        if let (Some(e1), Some(e2)) = (pair[0].meta.elevation, pair[1].meta.elevation) {
+            if run > 0.0 {
+                max_grade = max_grade.max((e2 - e1) / run as f32 * 100.0);
+            }
+        }
+    }
+    let distance = distance.round() as u32;
+    if distance == 0 {
+        return None;
+    }
+
+    let ascent = high - low;
+    let avg_grade = ascent / distance as f32 * 100.0;
+    if avg_grade < MIN_GRADE {
+        return None;
+    }
+    let category = Category::from(distance, avg_grade)?;
+    Some(Climb {
+        start,
+        end,
+        distance,
+        ascent,
+        avg_grade,
+        max_grade,
+        category,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::node::{self, Node};
+    use crate::graph::router::climb::{Category, detect};
+    use crate::osm::Coordinates;
+
+    /// Nodes every 100 meters northwards with the given elevations
+    fn nodes(elevations: &[f32]) -> Vec<Node> {
+        elevations.iter()
+            .enumerate()
+            .map(|(i, e)| Node::new(
+                i as i64,
+                Coordinates::from(48.0, 9.0).destination(0.0, i as f64 * 100.0),
+                node::Meta::new(Some(*e)),
+            ))
+            .collect()
+    }
+
+    #[test]
+    fn climbs() {
+        // flat, 2 km climb at 8.5% with a short dip, descent, 1 km climb at 9%, flat
+        let mut elevations = vec![100.0, 100.0];
+        elevations.extend((1..=20).map(|i| 100.0 + i as f32 * 8.5));
+        elevations[8] -= 15.0;
+        elevations.extend(&[250.0, 230.0]);
+        elevations.extend((1..=10).map(|i| 230.0 + i as f32 * 9.0));
+        elevations.extend(&[320.0, 320.0]);
+
+        let climbs = detect(&nodes(&elevations));
+        assert_eq!(climbs.len(), 2);
+        assert_eq!((climbs[0].start, climbs[0].end), (1, 21));
+        assert_eq!(climbs[0].category, Category::Three);
+        assert!((climbs[0].ascent - 170.0).abs() < 0.1);
+        assert!((climbs[0].avg_grade - 8.5).abs() < 0.1);
+        assert!(climbs[0].max_grade > 20.0);
+        assert_eq!((climbs[1].start, climbs[1].end), (23, 33));
+        assert_eq!(climbs[1].category, Category::Four);
+        assert!((climbs[1].avg_grade - 9.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn no_climbs() {
+        assert!(detect(&nodes(&[100.0, 102.0, 101.0, 103.0, 100.0])).is_empty());
+        assert!(detect(&nodes(&[200.0, 180.0, 150.0])).is_empty());
+        assert!(detect(&[]).is_empty());
+    }
+}
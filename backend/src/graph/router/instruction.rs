@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+
+use crate::graph::edge::Edge;
+use crate::graph::Graph;
+use crate::graph::router::options::{Params, Transport};
+use crate::graph::router::turn::Turn;
+use crate::osm::Highway::{Motorway, MotorwayLink, Trunk, TrunkLink};
+
+/// Turns below this deflection in degrees are slight, above sharp turns
+const SLIGHT_DEGREES: f32 = 45.0;
+const SHARP_DEGREES: f32 = 135.0;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Maneuver {
+    Depart,
+    /// Going straight on onto another road
+    Continue,
+    SlightLeft,
+    Left,
+    SharpLeft,
+    SlightRight,
+    Right,
+    SharpRight,
+    UTurn,
+    Roundabout,
+    /// From a motorway or trunk link onto the motorway or trunk
+    Merge,
+    /// Intermediate stop of a route with several stops
+    Waypoint,
+    Arrive,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Instruction {
+    pub maneuver: Maneuver,
+    /// Exit to take in a roundabout
+    pub exit: Option<u8>,
+    /// Name and ref of the road after the maneuver
+    pub road: Option<String>,
+    /// Index of the node of the route where the maneuver takes place
+    pub node: usize,
+    /// Distance and time until the next instruction
+    pub distance: u32,
+    pub time: u32,
+    pub text: String,
+}
+
+impl Instruction {
+    fn new(maneuver: Maneuver, exit: Option<u8>, road: Option<String>, node: usize) -> Self {
+        let mut instruction = Self {
+            maneuver,
+            exit,
+            road,
+            node,
+            distance: 0,
+            time: 0,
+            text: String::new(),
+        };
+        instruction.set_maneuver(maneuver);
+        instruction
+    }
+
+    pub fn set_maneuver(&mut self, maneuver: Maneuver) {
+        self.maneuver = maneuver;
+        let action = match maneuver {
+            Maneuver::Depart => "Head",
+            Maneuver::Continue => "Continue",
+            Maneuver::SlightLeft => "Turn slightly left",
+            Maneuver::Left => "Turn left",
+            Maneuver::SharpLeft => "Turn sharp left",
+            Maneuver::SlightRight => "Turn slightly right",
+            Maneuver::Right => "Turn right",
+            Maneuver::SharpRight => "Turn sharp right",
+            Maneuver::UTurn => "Make a U-turn",
+            Maneuver::Roundabout =>
+                return self.text = format!("At the roundabout, take exit {}{}",
+                                           self.exit.unwrap_or(1), self.onto()),
+            Maneuver::Merge => "Merge",
+            Maneuver::Waypoint => "Reach the stop and continue",
+            Maneuver::Arrive => return self.text = "Arrive at the destination".to_string(),
+        };
+        self.text = format!("{}{}", action, self.onto());
+    }
+
+    fn add(&mut self, edges: &[&Edge], params: &Params) {
+        for edge in edges {
+            self.distance += edge.distance();
+            self.time += edge.time(params);
+        }
+    }
+
+    fn onto(&self) -> String {
+        self.road.as_ref().map_or_else(String::new, |road| format!(" onto {}", road))
+    }
+}
+
+/// Instructions along the consecutive edges, the last one is always the arrival
+pub fn instructions(graph: &Graph, path: &[&Edge], params: &Params) -> Vec<Instruction> {
+    let mut instructions = vec![Instruction::new(Maneuver::Depart, None, road(graph, path[0]), 0)];
+
+    let mut i = 0;
+    while i < path.len() {
+        if i > 0 && path[i].meta.roundabout && !path[i - 1].meta.roundabout {
+            // the whole roundabout is a single maneuver
+            let mut exit = 0;
+            let mut j = i;
+            while j < path.len() && path[j].meta.roundabout {
+                if has_exit(graph, path[j].target_index, params.transport) {
+                    exit += 1;
+                }
+                j += 1;
+            }
+            let road = path.get(j).and_then(|e| road(graph, e));
+            let mut instruction = Instruction::new(Maneuver::Roundabout, Some(exit), road, i);
+            instruction.add(&path[i..j], params);
+            instructions.push(instruction);
+            i = j;
+            continue;
+        }
+        // leaving the roundabout is part of its instruction
+        if i > 0 && !path[i - 1].meta.roundabout {
+            if let Some(maneuver) = maneuver(graph, path[i - 1], path[i]) {
+                instructions.push(Instruction::new(maneuver, None, road(graph, path[i]), i));
+            }
+        }
+        instructions.last_mut().unwrap().add(&path[i..=i], params);
+        i += 1;
+    }
+    instructions.push(Instruction::new(Maneuver::Arrive, None, None, path.len()));
+    instructions
+}
+
+fn maneuver(graph: &Graph, from: &Edge, to: &Edge) -> Option<Maneuver> {
+    let is_same_road = from.meta.name == to.meta.name && from.meta.reference == to.meta.reference;
+    let is_merge = matches!(from.meta.highway, MotorwayLink | TrunkLink) &&
+        matches!(to.meta.highway, Motorway | Trunk);
+
+    let maneuver = match graph.turn(from, to) {
+        Turn::Straight if is_merge => Maneuver::Merge,
+        Turn::Straight if is_same_road => return None,
+        Turn::Straight => Maneuver::Continue,
+        // the road itself bends
+        Turn::Left(deflection) | Turn::Right(deflection)
+        if is_same_road && deflection < SLIGHT_DEGREES => return None,
+        Turn::Left(deflection) if deflection < SLIGHT_DEGREES => Maneuver::SlightLeft,
+        Turn::Left(deflection) if deflection < SHARP_DEGREES => Maneuver::Left,
+        Turn::Left(_) => Maneuver::SharpLeft,
+        Turn::Right(deflection) if deflection < SLIGHT_DEGREES => Maneuver::SlightRight,
+        Turn::Right(deflection) if deflection < SHARP_DEGREES => Maneuver::Right,
+        Turn::Right(_) => Maneuver::SharpRight,
+        Turn::Around => Maneuver::UTurn,
+    };
+    Some(maneuver)
+}
+
+/// Whether a road which isn't part of the roundabout leaves at the node
+fn has_exit(graph: &Graph, node_index: usize, transport: Transport) -> bool {
+    graph.edges(node_index).iter()
+        .any(|e| !e.meta.roundabout && e.allows(transport))
+}
+
+/// e.g. "Hauptstraße (B 500)"
+fn road(graph: &Graph, edge: &Edge) -> Option<String> {
+    let name = edge.meta.name.map(|n| graph.name(n));
+    let reference = edge.meta.reference.map(|r| graph.name(r));
+    match (name, reference) {
+        (Some(name), Some(reference)) => Some(format!("{} ({})", name, reference)),
+        (Some(name), None) => Some(name.to_string()),
+        (None, Some(reference)) => Some(reference.to_string()),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{fixtures, Graph};
+    use crate::graph::edge::Edge;
+    use crate::graph::router::instruction::{instructions, Maneuver};
+    use crate::graph::router::options::{Params, Routing, Transport};
+
+    fn car() -> Params {
+        Params::new(Transport::Car, Routing::Time, false)
+    }
+
+    fn name(graph: &mut Graph, source: usize, target: usize, name: Option<&str>,
+            reference: Option<&str>) {
+        let name = name.map(|n| graph.names.insert(n));
+        let reference = reference.map(|r| graph.names.insert(r));
+        for edge in graph.edges.iter_mut().filter(|e|
+            (e.source_index, e.target_index) == (source, target) ||
+                (e.source_index, e.target_index) == (target, source)) {
+            edge.meta.name = name;
+            edge.meta.reference = reference;
+        }
+    }
+
+    fn path<'a>(graph: &'a Graph, nodes: &[usize]) -> Vec<&'a Edge> {
+        nodes.windows(2)
+            .map(|n| graph.edges(n[0]).iter().find(|e| e.target_index == n[1]).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn turns() {
+        // west, center, north, east and far east node
+        let coordinates = [(48.0, 8.99), (48.0, 9.0), (48.01, 9.0), (48.0, 9.01), (48.0, 9.02)];
+        let edges = [
+            (0, 1, "secondary"),
+            (1, 2, "residential"),
+            (1, 3, "secondary"),
+            (3, 4, "secondary"),
+        ];
+        let mut graph = fixtures::graph(&coordinates, &edges);
+        name(&mut graph, 0, 1, Some("Main Street"), None);
+        name(&mut graph, 1, 2, Some("North Road"), None);
+        name(&mut graph, 1, 3, Some("Main Street"), None);
+        name(&mut graph, 3, 4, None, Some("B 500"));
+
+        let left = instructions(&graph, &path(&graph, &[0, 1, 2]), &car());
+        let maneuvers: Vec<Maneuver> = left.iter().map(|i| i.maneuver).collect();
+        assert_eq!(maneuvers, vec![Maneuver::Depart, Maneuver::Left, Maneuver::Arrive]);
+        assert_eq!(left[1].text, "Turn left onto North Road");
+        assert_eq!(left[1].node, 1);
+
+        let path = path(&graph, &[0, 1, 3, 4]);
+        let straight = instructions(&graph, &path, &car());
+        let maneuvers: Vec<Maneuver> = straight.iter().map(|i| i.maneuver).collect();
+        assert_eq!(maneuvers, vec![Maneuver::Depart, Maneuver::Continue, Maneuver::Arrive]);
+        assert_eq!(straight[0].text, "Head onto Main Street");
+        assert_eq!(straight[0].distance, path[0].distance() + path[1].distance());
+        assert_eq!(straight[1].text, "Continue onto B 500");
+        assert_eq!(straight[1].distance, path[2].distance());
+        assert_eq!(straight[2].node, 3);
+    }
+
+    #[test]
+    fn roundabout() {
+        // ring of west, south, east and north node with a road leaving at each of them
+        let coordinates = [
+            (48.0, 8.999), (47.999, 9.0), (48.0, 9.001), (48.001, 9.0),
+            (48.0, 8.99), (47.99, 9.0), (48.0, 9.01), (48.01, 9.0),
+        ];
+        let edges = [
+            (0, 1, "tertiary"), (1, 2, "tertiary"), (2, 3, "tertiary"), (3, 0, "tertiary"),
+            (4, 0, "tertiary"), (5, 1, "tertiary"), (6, 2, "tertiary"), (7, 3, "tertiary"),
+        ];
+        let mut graph = fixtures::graph(&coordinates, &edges);
+        for edge in graph.edges.iter_mut().filter(|e| e.source_index < 4 && e.target_index < 4) {
+            edge.meta.roundabout = true;
+        }
+        name(&mut graph, 3, 7, Some("North Road"), None);
+
+        let instructions = instructions(&graph, &path(&graph, &[4, 0, 1, 2, 3, 7]), &car());
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[1].maneuver, Maneuver::Roundabout);
+        assert_eq!(instructions[1].exit, Some(3));
+        assert_eq!(instructions[1].text, "At the roundabout, take exit 3 onto North Road");
+    }
+}
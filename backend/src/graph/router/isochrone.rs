@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::graph::Graph;
+use crate::graph::router::options::Params;
+use crate::graph::router::Router;
+use crate::graph::router::snap::interpolate;
+use crate::osm::Coordinates;
+
+/// Side length in meters of the cells the reachable edges are rasterized into
+const CELL_SIZE: f64 = 100.0;
+
+/// Edge which is at least partially reachable, costs are given in the unit of the routing
+#[derive(Debug)]
+pub struct Reach {
+    pub edge_index: usize,
+    /// Share of the edge before the point it is entered at, only set for the start edges
+    pub from: f64,
+    pub source_cost: u32,
+    pub target_cost: u32,
+}
+
+impl Reach {
+    /// Share of the edge up to which it is reachable within the budget
+    fn until(&self, budget: u32) -> Option<f64> {
+        if self.target_cost <= budget {
+            Some(1.0)
+        } else if self.source_cost < budget {
+            let share = f64::from(budget - self.source_cost) / f64::from(self.target_cost - self.source_cost);
+            Some(self.from + (1.0 - self.from) * share)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Contour {
+    pub budget: u32,
+    /// Outer rings of the reachable area, closed and counterclockwise
+    pub area: Vec<Vec<Coordinates>>,
+    /// Reachable parts of the edges as lines
+    pub edges: Vec<[Coordinates; 2]>,
+}
+
+/// Areas reachable from the start within each budget, given in seconds for time routing
+/// and meters otherwise. The area consists of the cells around the reachable edges.
+pub fn isochrones(graph: &Graph, params: &Params, start: &Coordinates, budgets: &[u32])
+                  -> Result<Vec<Contour>, &'static str> {
+    let max_budget = budgets.iter().max().ok_or("At least one budget is required")?;
+    let start = graph.snap(start, params)?;
+    let reached = Router::new(graph, params.clone()).explore(&start, *max_budget);
+
+    Ok(budgets.iter()
+        .map(|budget| {
+            let edges: Vec<[Coordinates; 2]> = reached.iter()
+                .filter_map(|r| {
+                    let until = r.until(*budget)?;
+                    let edge = graph.edge(r.edge_index);
+                    let source = graph.coordinates(edge.source_index);
+                    let target = graph.coordinates(edge.target_index);
+                    Some([interpolate(source, target, r.from), interpolate(source, target, until)])
+                })
+                .collect();
+            Contour {
+                budget: *budget,
+                area: Raster::new(&start.coordinates, &edges).outline(),
+                edges,
+            }
+        })
+        .collect())
+}
+
+/// Cells around the start, `x` is the longitude and `y` the latitude axis
+struct Raster<'a> {
+    origin: &'a Coordinates,
+    lat_step: f64,
+    lon_step: f64,
+    cells: BTreeSet<(i32, i32)>,
+}
+
+impl<'a> Raster<'a> {
+    /// Marks all cells along the lines and their neighbors, so the area has some margin
+    fn new(origin: &'a Coordinates, lines: &[[Coordinates; 2]]) -> Self {
+        let lat_step = CELL_SIZE / 111_320.0;
+        let mut raster = Self {
+            origin,
+            lat_step,
+            lon_step: lat_step / origin.lat().to_radians().cos(),
+            cells: BTreeSet::new(),
+        };
+        for [from, to] in lines {
+            let samples = (from.distance(to) / (CELL_SIZE / 2.0)).ceil() as usize;
+            for i in 0..=samples {
+                let point = interpolate(from, to, i as f64 / samples.max(1) as f64);
+                let (x, y) = raster.cell(&point);
+                for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        raster.cells.insert((x + dx, y + dy));
+                    }
+                }
+            }
+        }
+        raster
+    }
+
+    fn cell(&self, point: &Coordinates) -> (i32, i32) {
+        (((point.lon() - self.origin.lon()) / self.lon_step).floor() as i32,
+         ((point.lat() - self.origin.lat()) / self.lat_step).floor() as i32)
+    }
+
+    fn corner(&self, (x, y): (i32, i32)) -> Coordinates {
+        Coordinates::from(self.origin.lat() + f64::from(y) * self.lat_step,
+                          self.origin.lon() + f64::from(x) * self.lon_step)
+    }
+
+    /// Outer rings around the marked cells, holes are filled
+    fn outline(&self) -> Vec<Vec<Coordinates>> {
+        // boundary sides of the cells, directed so the cell lies to the left
+        let mut sides: BTreeMap<(i32, i32), Vec<(i32, i32)>> = BTreeMap::new();
+        for &(x, y) in &self.cells {
+            let mut side = |from: (i32, i32), to: (i32, i32), neighbor: (i32, i32)| {
+                if !self.cells.contains(&neighbor) {
+                    sides.entry(from).or_default().push(to);
+                }
+            };
+            side((x, y), (x + 1, y), (x, y - 1));
+            side((x + 1, y), (x + 1, y + 1), (x + 1, y));
+            side((x + 1, y + 1), (x, y + 1), (x, y + 1));
+            side((x, y + 1), (x, y), (x - 1, y));
+        }
+
+        let mut rings = Vec::new();
+        while let Some(&start) = sides.keys().next() {
+            let ring = trace(&mut sides, start);
+            if area(&ring) > 0 {
+                let mut ring: Vec<Coordinates> = ring.into_iter().map(|c| self.corner(c)).collect();
+                ring.push(ring[0].clone());
+                rings.push(ring);
+            }
+        }
+        rings
+    }
+}
+
+/// Follows the sides from the start until the ring is closed, preferring left turns
+/// so cells which only touch at a corner stay separate. Only corners are returned.
+fn trace(sides: &mut BTreeMap<(i32, i32), Vec<(i32, i32)>>, start: (i32, i32)) -> Vec<(i32, i32)> {
+    let mut ring = Vec::new();
+    let mut current = start;
+    let mut direction: Option<(i32, i32)> = None;
+    while let Some(targets) = sides.get_mut(&current) {
+        let position = direction.and_then(|(dx, dy)| {
+            [(-dy, dx), (dx, dy), (dy, -dx)].iter()
+                .find_map(|d| targets.iter().position(|t| (t.0 - current.0, t.1 - current.1) == *d))
+        }).unwrap_or(0);
+        let next = targets.remove(position);
+        if targets.is_empty() {
+            sides.remove(&current);
+        }
+
+        let next_direction = (next.0 - current.0, next.1 - current.1);
+        if direction != Some(next_direction) {
+            ring.push(current);
+        }
+        direction = Some(next_direction);
+        current = next;
+        if current == start {
+            break;
+        }
+    }
+    ring
+}
+
+/// Twice the signed area, positive for counterclockwise rings
+fn area(ring: &[(i32, i32)]) -> i64 {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| i64::from(a.0) * i64::from(b.1) - i64::from(b.0) * i64::from(a.1))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::fixtures;
+    use crate::graph::router::isochrone::{area, isochrones, Raster};
+    use crate::graph::router::options::{Params, Routing, Transport};
+    use crate::osm::Coordinates;
+
+    #[test]
+    fn contours() {
+        let graph = fixtures::grid(12);
+        let params = Params::new(Transport::Car, Routing::Distance, false);
+        let start = graph.coordinates(66).clone();
+        let contours = isochrones(&graph, &params, &start, &[500, 1500]).unwrap();
+
+        let (small, large) = (&contours[0], &contours[1]);
+        assert_eq!(small.budget, 500);
+        assert!(small.edges.len() < large.edges.len());
+        for [from, _] in &small.edges {
+            // the beeline can't be longer than the reachable distance
+            assert!(start.distance(from) <= 500.0);
+        }
+        for contour in &contours {
+            assert!(!contour.area.is_empty());
+            for ring in &contour.area {
+                assert_eq!(ring.first(), ring.last());
+            }
+        }
+    }
+
+    #[test]
+    fn outline() {
+        let origin = Coordinates::from(48.0, 9.0);
+        let mut raster = Raster::new(&origin, &[]);
+        // 3x3 block with a hole in the middle and a separate cell touching a corner
+        for x in 0..3 {
+            for y in 0..3 {
+                if (x, y) != (1, 1) {
+                    raster.cells.insert((x, y));
+                }
+            }
+        }
+        raster.cells.insert((3, 3));
+
+        let rings = raster.outline();
+        assert_eq!(rings.len(), 2);
+        let corners: Vec<usize> = rings.iter().map(|r| r.len() - 1).collect();
+        assert_eq!(corners, vec![4, 4]);
+        let ring: Vec<(i32, i32)> = vec![(0, 0), (3, 0), (3, 3), (0, 3)];
+        assert_eq!(area(&ring), 18);
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::graph::{Edge, Graph};
+use crate::graph::router::options::Params;
+use crate::graph::router::route::{Route, RouteBuilder};
+use crate::graph::router::RouterNode;
+use crate::osm::Coordinates;
+
+/// Edges further away from a GPS point in meters are no candidates
+const CANDIDATE_RADIUS: f64 = 50.0;
+/// Both directions of the roads at an intersection should fit in
+const MAX_CANDIDATES: usize = 10;
+/// Standard deviation of the GPS noise in meters
+const SIGMA: f64 = 10.0;
+/// Scale of the difference between route and beeline distance in meters
+const BETA: f64 = 20.0;
+/// Routes between consecutive points may be at most this much longer than the beeline
+const MAX_DETOUR: f64 = 2.0;
+
+/// Matches a noisy GPS trace onto the graph with a hidden Markov model, i.e. the hidden states
+/// are candidate edges close to each point and the most likely sequence is found via Viterbi.
+/// Points without candidates or without any route from the previous point are skipped.
+pub fn match_trace(graph: &Graph, params: &Params, trace: &[Coordinates]) -> Result<Route, &'static str> {
+    // candidates of all points which could be matched
+    let mut columns: Vec<Vec<Candidate>> = Vec::new();
+    let mut previous_point: Option<&Coordinates> = None;
+
+    for point in trace {
+        let mut candidates = candidates(graph, params, point);
+        if candidates.is_empty() {
+            continue;
+        }
+        if let (Some(previous_point), Some(previous)) = (previous_point, columns.last()) {
+            let beeline = previous_point.distance(point);
+            let limit = (beeline * MAX_DETOUR + 2.0 * CANDIDATE_RADIUS) as u32;
+            let searches: Vec<Search> = previous.iter()
+                .map(|c| Search::new(graph, params, c, limit))
+                .collect();
+
+            for candidate in &mut candidates {
+                for (i, (from, search)) in previous.iter().zip(&searches).enumerate() {
+                    let distance = if let Some(d) = search.distance(graph, from, candidate) { d } else {
+                        continue;
+                    };
+                    let transition = -(distance - beeline).abs() / BETA;
+                    let probability = from.probability + transition + candidate.emission;
+                    if probability > candidate.probability {
+                        candidate.probability = probability;
+                        candidate.prev = Some(i);
+                        candidate.via = search.path(graph, from, candidate);
+                    }
+                }
+            }
+            candidates.retain(|c| c.prev.is_some());
+            if candidates.is_empty() {
+                // most likely an outlier
+                continue;
+            }
+        } else {
+            for candidate in &mut candidates {
+                candidate.probability = candidate.emission;
+            }
+        }
+        columns.push(candidates);
+        previous_point = Some(point);
+    }
+
+    let path = backtrack(graph, &columns)?;
+    Ok(RouteBuilder::new(graph, params).build(&path))
+}
+
+#[derive(Debug, Clone)]
+struct Candidate {
+    edge_index: usize,
+    /// Distance from the source of the edge to the projected point in meters
+    offset: f64,
+    /// Log-probabilities
+    emission: f64,
+    probability: f64,
+    /// Index of the most likely candidate of the previous point
+    prev: Option<usize>,
+    /// Edges between the edge of the previous candidate and this one
+    via: Vec<usize>,
+}
+
+fn candidates(graph: &Graph, params: &Params, point: &Coordinates) -> Vec<Candidate> {
+    graph.edges_within(point, params, CANDIDATE_RADIUS).into_iter()
+        .take(MAX_CANDIDATES)
+        .map(|(edge_index, distance, fraction)| Candidate {
+            edge_index,
+            offset: fraction * f64::from(graph.edge(edge_index).distance()),
+            emission: -0.5 * (distance / SIGMA).powi(2),
+            probability: f64::NEG_INFINITY,
+            prev: None,
+            via: Vec::new(),
+        })
+        .collect()
+}
+
+/// Dijkstra from the target of a candidate's edge, limited to the given distance
+struct Search {
+    /// Distance and last edge of the shortest path per node
+    distances: HashMap<usize, (u32, Option<usize>)>,
+}
+
+impl Search {
+    fn new(graph: &Graph, params: &Params, from: &Candidate, limit: u32) -> Self {
+        let edge = graph.edge(from.edge_index);
+        let mut distances = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        distances.insert(edge.target_index, (0, None));
+        queue.push(RouterNode::new(edge.target_index, 0, 0));
+
+        while let Some(node) = queue.pop() {
+            if node.cost > distances[&node.index].0 || node.cost > limit {
+                continue;
+            }
+            for edge_index in graph.edge_indices(node.index) {
+                let next = graph.edge(edge_index);
+                if !next.is_relevant(params) {
+                    continue;
+                }
+                let distance = node.cost + next.distance();
+                if distances.get(&next.target_index).is_none_or(|(d, _)| distance < *d) {
+                    distances.insert(next.target_index, (distance, Some(edge_index)));
+                    queue.push(RouterNode::new(next.target_index, distance, 0));
+                }
+            }
+        }
+        Self { distances }
+    }
+
+    /// Going slightly back on the same edge is considered as GPS noise
+    fn is_same_edge(from: &Candidate, to: &Candidate) -> bool {
+        from.edge_index == to.edge_index && to.offset >= from.offset - 2.0 * SIGMA
+    }
+
+    /// Route distance from the one candidate to the other
+    fn distance(&self, graph: &Graph, from: &Candidate, to: &Candidate) -> Option<f64> {
+        if Self::is_same_edge(from, to) {
+            return Some((to.offset - from.offset).max(0.0));
+        }
+        let remaining = f64::from(graph.edge(from.edge_index).distance()) - from.offset;
+        let source = graph.edge(to.edge_index).source_index;
+        self.distances.get(&source)
+            .map(|(distance, _)| remaining + f64::from(*distance) + to.offset)
+    }
+
+    /// Edges between the edges of both candidates
+    fn path(&self, graph: &Graph, from: &Candidate, to: &Candidate) -> Vec<usize> {
+        let mut path = Vec::new();
+        if Self::is_same_edge(from, to) {
+            return path;
+        }
+        let mut node_index = graph.edge(to.edge_index).source_index;
+        while let Some((_, Some(edge_index))) = self.distances.get(&node_index) {
+            path.push(*edge_index);
+            node_index = graph.edge(*edge_index).source_index;
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// Edges along the most likely sequence of candidates
+fn backtrack<'a>(graph: &'a Graph, columns: &[Vec<Candidate>]) -> Result<Vec<&'a Edge>, &'static str> {
+    if columns.len() < 2 {
+        return Err("Trace couldn't be matched onto the map");
+    }
+    let last = columns.last().unwrap();
+    let mut index = (0..last.len())
+        .max_by(|a, b| last[*a].probability.partial_cmp(&last[*b].probability).unwrap())
+        .unwrap();
+    let mut states = Vec::with_capacity(columns.len());
+    for column in columns.iter().rev() {
+        let candidate = &column[index];
+        states.push(candidate);
+        index = candidate.prev.unwrap_or(0);
+    }
+    states.reverse();
+
+    let mut path = vec![states[0].edge_index];
+    for pair in states.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        if Search::is_same_edge(from, to) {
+            continue;
+        }
+        path.extend(&to.via);
+        path.push(to.edge_index);
+    }
+
+    // the first and last point may be matched onto barely traversed edges due to GPS noise
+    let first = states.first().unwrap();
+    let first_distance = f64::from(graph.edge(first.edge_index).distance());
+    if path.len() > 1 && first.offset >= first_distance - 2.0 * SIGMA {
+        path.remove(0);
+    }
+    if path.len() > 1 && states.last().unwrap().offset <= 2.0 * SIGMA {
+        path.pop();
+    }
+    Ok(path.into_iter().map(|i| graph.edge(i)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{fixtures, Graph};
+    use crate::graph::fixtures::Random;
+    use crate::graph::router;
+    use crate::graph::router::matching::match_trace;
+    use crate::graph::router::options::{Params, Routing, Transport};
+    use crate::graph::router::route::Route;
+    use crate::osm::Coordinates;
+
+    /// Points every `spacing` meters along the route, moved by up to `noise` meters
+    fn trace(route: &Route, spacing: f64, noise: f64, random: &mut Random) -> Vec<Coordinates> {
+        let mut trace = Vec::new();
+        for pair in route.nodes.windows(2) {
+            let (from, to) = (&pair[0].coordinates, &pair[1].coordinates);
+            let steps = (from.distance(to) / spacing).ceil() as usize;
+            for step in 0..steps {
+                let fraction = step as f64 / steps as f64;
+                let point = Coordinates::from(
+                    from.lat() + fraction * (to.lat() - from.lat()),
+                    from.lon() + fraction * (to.lon() - from.lon()),
+                );
+                let bearing = random.next() * 360.0;
+                trace.push(point.destination(bearing, random.next() * noise));
+            }
+        }
+        trace.push(route.nodes.last().unwrap().coordinates.clone());
+        trace
+    }
+
+    fn route(graph: &Graph, params: &Params, start: usize, goal: usize) -> Route {
+        router::shortest_path(graph, params, graph.coordinates(start), graph.coordinates(goal))
+            .unwrap()
+    }
+
+    #[test]
+    fn noisy_traces() {
+        let graph = fixtures::grid(10);
+        let params = Params::new(Transport::Car, Routing::Distance, false);
+        let mut random = Random(7);
+
+        for (start, goal) in &[(0, 99), (9, 90), (45, 3), (72, 27)] {
+            let expected = route(&graph, &params, *start, *goal);
+            let trace = trace(&expected, 25.0, 15.0, &mut random);
+            let matched = match_trace(&graph, &params, &trace).unwrap();
+            assert_eq!(matched.edges, expected.edges);
+        }
+    }
+
+    #[test]
+    fn sparse_trace_with_outlier() {
+        let graph = fixtures::grid(10);
+        let params = Params::new(Transport::Car, Routing::Distance, false);
+        let mut random = Random(11);
+        let expected = route(&graph, &params, 0, 99);
+
+        // roughly one point per edge and a point far off to the side of the route
+        let mut trace = trace(&expected, 200.0, 10.0, &mut random);
+        let middle = trace.len() / 2;
+        let (before, after) = (&trace[middle - 1], &trace[middle]);
+        let is_north_south = (after.lat() - before.lat()).abs() >
+            (after.lon() - before.lon()).abs() * before.lat().to_radians().cos();
+        let outlier = before.destination(if is_north_south { 90.0 } else { 0.0 }, 150.0);
+        trace.insert(middle, outlier);
+        let matched = match_trace(&graph, &params, &trace).unwrap();
+        assert_eq!(matched.edges, expected.edges);
+    }
+
+    #[test]
+    fn unmatchable_trace() {
+        let graph = fixtures::grid(5);
+        let params = Params::new(Transport::Car, Routing::Distance, false);
+        let far_away = [Coordinates::from(50.0, 10.0), Coordinates::from(50.01, 10.0)];
+        assert!(match_trace(&graph, &params, &far_away).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use serde::Serialize;
+
+use crate::graph::Graph;
+use crate::graph::router::options::Params;
+use crate::graph::router::Router;
+use crate::graph::router::snap::{self, Snap};
+use crate::osm::Coordinates;
+
+/// Travel times in seconds and distances in meters from every source (row) to every target
+/// (column), `None` if the target is unreachable from the source
+#[derive(Debug, Serialize)]
+pub struct Matrix {
+    pub times: Vec<Vec<Option<u32>>>,
+    pub distances: Vec<Vec<Option<u32>>>,
+}
+
+/// One search per source which finds all targets at once
+pub fn matrix(graph: &Graph, params: &Params, sources: &[Coordinates], targets: &[Coordinates])
+              -> Result<Matrix, &'static str> {
+    let snap = |coords| graph.snap(coords, params);
+    let sources: Vec<Snap> = sources.iter().map(snap).collect::<Result<_, _>>()?;
+    let targets: Vec<Snap> = targets.iter().map(snap).collect::<Result<_, _>>()?;
+
+    let mut router = Router::new(graph, params.clone());
+    let mut matrix = Matrix {
+        times: Vec::with_capacity(sources.len()),
+        distances: Vec::with_capacity(sources.len()),
+    };
+    for source in &sources {
+        let (times, distances) = router.search_many(source, &targets).into_iter()
+            .zip(&targets)
+            .map(|(path, target)| match path {
+                Some(path) => {
+                    let edges = snap::trim(graph, &path, source, target);
+                    (Some(edges.iter().map(|e| e.time(params)).sum()),
+                     Some(edges.iter().map(|e| e.distance()).sum()))
+                }
+                None => (None, None)
+            })
+            .unzip();
+        matrix.times.push(times);
+        matrix.distances.push(distances);
+    }
+    Ok(matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::fixtures;
+    use crate::graph::router::matrix::matrix;
+    use crate::graph::router::options::{Params, Routing, Transport};
+    use crate::graph::router::Router;
+    use crate::osm::Coordinates;
+
+    #[test]
+    fn same_as_shortest_paths() {
+        let graph = fixtures::grid(8);
+        let between = |a: usize, b: usize| {
+            let (a, b) = (graph.coordinates(a), graph.coordinates(b));
+            Coordinates::from(a.lat() * 0.3 + b.lat() * 0.7, a.lon() * 0.3 + b.lon() * 0.7)
+        };
+        let sources = vec![graph.coordinates(0).clone(), between(27, 28), graph.coordinates(63).clone()];
+        let targets = vec![between(28, 27), graph.coordinates(7).clone(), between(40, 48), between(0, 1)];
+
+        for routing in &[Routing::Time, Routing::Distance] {
+            let params = Params::new(Transport::Car, *routing, false);
+            let matrix = matrix(&graph, &params, &sources, &targets).unwrap();
+            let mut router = Router::new(&graph, params.clone());
+            for (i, source) in sources.iter().enumerate() {
+                for (j, target) in targets.iter().enumerate() {
+                    let route = router.shortest_path(source, target).ok();
+                    let (time, distance) = (matrix.times[i][j], matrix.distances[i][j]);
+                    if *routing == Routing::Time {
+                        assert_eq!(time, route.map(|r| r.time));
+                    } else {
+                        assert_eq!(distance, route.map(|r| r.distance));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unreachable() {
+        // two separate roads
+        let coordinates = [(48.0, 9.0), (48.0, 9.01), (48.1, 9.0), (48.1, 9.01)];
+        let edges = [(0, 1, "residential"), (2, 3, "residential")];
+        let graph = fixtures::graph(&coordinates, &edges);
+        let params = Params::new(Transport::Car, Routing::Distance, false);
+        let points: Vec<Coordinates> = (0..4).map(|i| graph.coordinates(i).clone()).collect();
+
+        let matrix = matrix(&graph, &params, &points[..2], &points).unwrap();
+        let length = graph.edge(0).distance();
+        assert_eq!(matrix.distances[0], vec![Some(0), Some(length), None, None]);
+        assert_eq!(matrix.distances[1], vec![Some(length), Some(0), None, None]);
+        assert_eq!(matrix.times[0][2], None);
+    }
+}
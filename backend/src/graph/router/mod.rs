@@ -1,91 +1,417 @@
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
-use std::mem;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 
 use crate::graph::{Edge, Graph};
-use crate::graph::router::options::Params;
+use crate::graph::landmarks::Landmarks;
+use crate::graph::router::ch::ChRouter;
+use crate::graph::router::options::{Params, SpeedModel};
 use crate::graph::router::options::Routing::Time;
-use crate::graph::router::options::Transport::Car;
+use crate::graph::edge::{BIKE_SPEED, WALK_SPEED};
+use crate::graph::speed::{MAX_BIKE_SPEED, MAX_WALK_SPEED};
+use crate::graph::router::options::Transport::{Bike, Car};
+use crate::graph::router::isochrone::Reach;
 use crate::graph::router::route::{Route, RouteBuilder};
+use crate::graph::router::snap::Snap;
 use crate::osm::Coordinates;
 
-pub mod grid;
+pub mod alternatives;
+pub mod area;
+pub mod ch;
+pub mod climb;
+pub mod instruction;
+pub mod isochrone;
+pub mod matching;
+pub mod matrix;
+pub mod round_trip;
 pub mod route;
 pub mod options;
+pub mod snap;
+pub mod spatial;
+pub mod tsp;
+pub mod turn;
 
+/// Uses the contraction hierarchy matching the params if there is one
+pub fn shortest_path(graph: &Graph, params: &Params, start: &Coordinates, goal: &Coordinates)
+                     -> Result<Route, &'static str> {
+    if let Some(hierarchy) = graph.hierarchy(params) {
+        let route = ChRouter::new(graph, hierarchy).shortest_path(start, goal)?;
+        // hierarchies are node-based and therefore don't know about turn restrictions
+        if graph.is_path_allowed(&route.edges, params.transport) {
+            return Ok(route);
+        }
+    }
+    Router::new(graph, params.clone()).shortest_path(start, goal)
+}
+
+/// Edge-based A*, i.e. every search node is an edge together with the node it leads to,
+/// which allows to respect turn restrictions
 pub struct Router<'a> {
     graph: &'a Graph,
     params: Params,
+    landmarks: Option<&'a Landmarks>,
+    /// Plain Dijkstra if disabled
+    use_heuristic: bool,
 
     queue: BinaryHeap<RouterNode>,
-    cost: Vec<u32>,
-    prev: Vec<Option<&'a Edge>>,
+    /// Tentative cost and the previous edge of every edge reached so far
+    settled: HashMap<usize, (u32, Option<usize>)>,
+    /// Cost factors of edges which should be avoided, always at least 1
+    penalties: HashMap<usize, f32>,
+    /// Edges within the areas to avoid
+    blocked: HashSet<usize>,
+    /// Edges the search starts on with the share which is left to traverse
+    start: HashMap<usize, f64>,
+    goal: Option<Goal>,
+}
+
+/// Target of a search, either a node or a point on an edge
+struct Goal {
+    coordinates: Coordinates,
+    /// Every path to the goal passes one of the nodes
+    nodes: Vec<usize>,
+    /// Edges leading to the point with their share up to it, empty if the goal is a node
+    edges: HashMap<usize, f64>,
 }
 
 impl<'a> Router<'a> {
     pub fn new(graph: &'a Graph, params: Params) -> Self {
-        let mut prev = Vec::with_capacity(graph.nodes.len());
-        prev.resize(graph.nodes.len(), None);
+        let blocked = graph.edges_in(&params.avoid_areas);
         Self {
             graph,
+            landmarks: graph.landmarks(&params),
             params,
+            use_heuristic: true,
 
-            queue: BinaryHeap::with_capacity(graph.nodes.len()),
-            cost: vec![u32::max_value(); graph.nodes.len()],
-            prev,
+            queue: BinaryHeap::new(),
+            settled: HashMap::new(),
+            penalties: HashMap::new(),
+            blocked,
+            start: HashMap::new(),
+            goal: None,
         }
     }
 
-    pub fn shortest_path(&mut self, start: &Coordinates, goal: &Coordinates) -> Result<Route, &str> {
-        let start_index = self.graph.nearest_neighbor(start, &self.params)?;
-        let start_id = self.graph.node(start_index).id;
-        let goal_index = self.graph.nearest_neighbor(goal, &self.params)?;
-        let goal_id = self.graph.node(goal_index).id;
-        if start_id == goal_id {
+    pub fn shortest_path(&mut self, start: &Coordinates, goal: &Coordinates) -> Result<Route, &'static str> {
+        let start = self.graph.snap(start, &self.params)?;
+        let goal = self.graph.snap(goal, &self.params)?;
+        let path = self.search_snapped(&start, &goal)?;
+        Ok(RouteBuilder::new(self.graph, &self.params).build_snapped(&path, &start, &goal))
+    }
+
+    /// Edges of the cheapest path between the two snapped points, the first and last edge
+    /// are the ones the points lie on unless they lie on a node
+    pub fn search_snapped(&mut self, start: &Snap, goal: &Snap) -> Result<Vec<&'a Edge>, &'static str> {
+        self.search_from(start, None, goal)
+    }
+
+    /// Like `search_snapped`, but the start is left onwards instead of
+    /// turning back on the edge it was reached by
+    pub fn search_onward(&mut self, arrival: &Edge, start: &Snap, goal: &Snap)
+                         -> Result<Vec<&'a Edge>, &'static str> {
+        self.search_from(start, Some(arrival), goal)
+    }
+
+    fn search_from(&mut self, start: &Snap, arrival: Option<&Edge>, goal: &Snap)
+                   -> Result<Vec<&'a Edge>, &'static str> {
+        if start.coordinates == goal.coordinates {
             return Err("No path found, start is goal");
         }
+        if let Some(edge_index) = snap::direct(self.graph, &self.params, start, goal) {
+            if arrival.is_none_or(|a| !is_reverse(self.graph.edge(edge_index), a)) {
+                return Ok(vec![self.graph.edge(edge_index)]);
+            }
+        }
 
-        self.cost[start_index] = 0;
-        self.queue.push(RouterNode::new(start_index, 0, 0));
-        while let Some(node) = self.queue.pop() {
-            let id = self.graph.node(node.index).id;
-            if id == goal_id {
-                let route = RouteBuilder::new(self.graph, &self.prev, self.params.transport)
-                    .build(start_index, node.index);
-                return Ok(route);
+        let goal = match goal.node_index(self.graph) {
+            Some(goal_index) => Goal {
+                coordinates: goal.coordinates.clone(),
+                nodes: vec![goal_index],
+                edges: HashMap::new(),
+            },
+            None => {
+                let edge = self.graph.edge(goal.edge_index);
+                Goal {
+                    coordinates: goal.coordinates.clone(),
+                    nodes: vec![edge.source_index, edge.target_index],
+                    edges: goal.edges(self.graph).into_iter().collect(),
+                }
             }
-            // better solution already found
-            if node.cost > self.cost[node.index] {
+        };
+        self.reset(Some(goal));
+        self.seed(start, arrival);
+        self.run()
+    }
+
+    /// Paths from the start to each of the goals, `None` if a goal is unreachable.
+    /// All goals are found by a single one-to-many search.
+    pub fn search_many(&mut self, start: &Snap, goals: &[Snap]) -> Vec<Option<Vec<&'a Edge>>> {
+        let mut paths = vec![None; goals.len()];
+        // goals per edge leading to them with the share of the edge up to the goal
+        let mut entries: HashMap<usize, Vec<(usize, f64)>> = HashMap::new();
+        let mut remaining = vec![0; goals.len()];
+        for (i, goal) in goals.iter().enumerate() {
+            if start.coordinates == goal.coordinates {
+                paths[i] = Some(Vec::new());
                 continue;
             }
-
-            for edge in self.graph.edges(node.index) {
-                if !edge.is_relevant(&self.params) {
-                    continue;
+            if let Some(edge_index) = snap::direct(self.graph, &self.params, start, goal) {
+                paths[i] = Some(vec![self.graph.edge(edge_index)]);
+                continue;
+            }
+            let goal_entries: Vec<(usize, f64)> = match goal.node_index(self.graph) {
+                Some(goal_index) => self.graph.edges(goal_index).iter()
+                    .flat_map(|e| self.graph.edges_between(e.target_index, goal_index))
+                    .map(|i| (i, 1.0))
+                    .collect(),
+                None => goal.edges(self.graph),
+            };
+            for (edge_index, share) in goal_entries {
+                if self.graph.edge(edge_index).is_relevant(&self.params) {
+                    entries.entry(edge_index).or_default().push((i, share));
+                    remaining[i] += 1;
                 }
+            }
+        }
 
-                let cost = node.cost +
-                    edge.cost(self.params.transport, self.params.routing);
-                if cost < self.cost[edge.target_index] {
-                    let heuristic = self.heuristic(edge.target_index, goal_index);
-                    let next = RouterNode::new(edge.target_index, cost, heuristic);
-                    let _ = mem::replace(&mut self.prev[next.index], Some(edge));
-                    self.cost[next.index] = next.cost;
-                    self.queue.push(next);
+        self.reset(None);
+        self.seed(start, None);
+        let mut best: Vec<Option<(u32, usize)>> = vec![None; goals.len()];
+        let mut unsettled: usize = remaining.iter().sum();
+        while let Some(node) = self.queue.pop() {
+            if unsettled == 0 {
+                break;
+            }
+            if node.cost > self.settled[&node.index].0 {
+                continue;
+            }
+            let prev_index = self.settled[&node.index].1;
+            let is_start = prev_index.is_none() && self.start.contains_key(&node.index);
+            if let Some(goal_entries) = entries.get(&node.index) {
+                let edge_cost = self.edge_cost(prev_index, node.index);
+                for (i, share) in goal_entries {
+                    unsettled -= 1;
+                    if is_start && *share < 1.0 {
+                        // the goal lies behind the start on the same edge
+                        continue;
+                    }
+                    let cost = node.cost - edge_cost + snap::partial_cost(edge_cost, *share);
+                    if best[*i].is_none_or(|(c, _)| cost < c) {
+                        best[*i] = Some((cost, node.index));
+                    }
                 }
             }
+            self.expand(&node);
+        }
+
+        for (i, best) in best.into_iter().enumerate() {
+            if let Some((_, edge_index)) = best {
+                paths[i] = Some(self.path(edge_index));
+            }
+        }
+        paths
+    }
+
+    /// All edges which are at least partially reachable from the start within the budget,
+    /// i.e. a one-to-all search which only expands edges ending within the budget
+    pub fn explore(&mut self, start: &Snap, budget: u32) -> Vec<Reach> {
+        self.reset(None);
+        self.seed(start, None);
+
+        let mut reached = Vec::new();
+        while let Some(node) = self.queue.pop() {
+            if node.cost > self.settled[&node.index].0 {
+                continue;
+            }
+            let prev_index = self.settled[&node.index].1;
+            let source_cost = node.cost - self.edge_cost(prev_index, node.index);
+            if source_cost > budget {
+                // turning onto the edge already exceeds the budget
+                continue;
+            }
+            let from = match prev_index {
+                None => self.start.get(&node.index).map_or(0.0, |share| 1.0 - share),
+                Some(_) => 0.0,
+            };
+            reached.push(Reach {
+                edge_index: node.index,
+                from,
+                source_cost,
+                target_cost: node.cost,
+            });
+            if node.cost <= budget {
+                self.expand(&node);
+            }
+        }
+        reached
+    }
+
+    fn reset(&mut self, goal: Option<Goal>) {
+        self.queue.clear();
+        self.settled.clear();
+        self.start.clear();
+        self.goal = goal;
+    }
+
+    /// Starts the search at the node or at the edges the point lies on,
+    /// except for the reverse of the edge the start was reached by
+    fn seed(&mut self, start: &Snap, arrival: Option<&Edge>) {
+        let graph = self.graph;
+        let is_onward = |edge_index: &usize| arrival.is_none_or(|a| !is_reverse(graph.edge(*edge_index), a));
+        if let Some(start_index) = start.node_index(self.graph) {
+            for edge_index in self.graph.edge_indices(start_index).filter(is_onward) {
+                self.relax(None, edge_index, 0);
+            }
+        } else {
+            self.start = start.edges(self.graph).into_iter()
+                .filter(|(i, _)| is_onward(i))
+                .map(|(i, fraction)| (i, 1.0 - fraction))
+                .collect();
+            let edges: Vec<usize> = self.start.keys().copied().collect();
+            for edge_index in edges {
+                self.relax(None, edge_index, 0);
+            }
+        }
+    }
+
+    fn run(&mut self) -> Result<Vec<&'a Edge>, &'static str> {
+        while let Some(node) = self.queue.pop() {
+            if self.is_goal(node.index) {
+                return Ok(self.path(node.index));
+            }
+            // better solution already found
+            if node.cost > self.settled[&node.index].0 {
+                continue;
+            }
+            self.expand(&node);
         }
         Err("No path found")
     }
 
-    fn heuristic(&self, from: usize, to: usize) -> u32 {
-        if self.params.transport == Car && self.params.routing == Time {
-            0
+    fn expand(&mut self, node: &RouterNode) {
+        let edge = self.graph.edge(node.index);
+        let is_dead_end = self.graph.edges(edge.target_index).len() == 1;
+        for next_index in self.graph.edge_indices(edge.target_index) {
+            let next = self.graph.edge(next_index);
+            if next.target_index == edge.source_index && !is_dead_end && self.params.turn_costs.is_none() {
+                // without turn costs turning back is only reasonable at dead ends,
+                // with them it's charged as a u-turn
+                continue;
+            }
+            if self.graph.is_turn_allowed(edge.source_index, edge.target_index,
+                                          next.target_index, self.params.transport) {
+                let cost = node.cost + self.turn_cost(edge, next);
+                self.relax(Some(node.index), next_index, cost);
+            }
+        }
+    }
+
+    fn is_goal(&self, edge_index: usize) -> bool {
+        let goal = self.goal.as_ref().unwrap();
+        if goal.edges.is_empty() {
+            return self.graph.edge(edge_index).target_index == goal.nodes[0];
+        }
+        // the goal lies behind the start if the search didn't leave its start edge
+        let is_start = self.settled[&edge_index].1.is_none() && self.start.contains_key(&edge_index);
+        goal.edges.contains_key(&edge_index) && !is_start
+    }
+
+    /// Multiplies the cost of the edge by the factor in subsequent searches
+    pub fn penalize(&mut self, edge_index: usize, factor: f32) {
+        let penalty = self.penalties.entry(edge_index).or_insert(1.0);
+        *penalty *= factor;
+    }
+
+    fn relax(&mut self, prev_index: Option<usize>, edge_index: usize, cost: u32) {
+        let edge = self.graph.edge(edge_index);
+        if !edge.is_relevant(&self.params) || self.blocked.contains(&edge_index) {
+            return;
+        }
+
+        let cost = cost + self.edge_cost(prev_index, edge_index);
+        if self.settled.get(&edge_index).is_none_or(|(c, _)| cost < *c) {
+            // edges with the goal point aren't traversed up to their target,
+            // so the distance of the target to the goal isn't a lower bound
+            let is_goal_edge = self.goal.as_ref().is_some_and(|g| g.edges.contains_key(&edge_index));
+            let heuristic = if is_goal_edge { 0 } else { self.heuristic(edge.target_index) };
+            self.settled.insert(edge_index, (cost, prev_index));
+            self.queue.push(RouterNode::new(edge_index, cost, heuristic));
+        }
+    }
+
+    fn edge_cost(&self, prev_index: Option<usize>, edge_index: usize) -> u32 {
+        let edge = self.graph.edge(edge_index);
+        let edge_cost = match self.penalties.get(&edge_index) {
+            Some(factor) => (edge.cost(&self.params) as f32 * factor).round() as u32,
+            None => edge.cost(&self.params),
+        };
+        // only parts of the edges with the start or goal point are traversed
+        let share = match prev_index {
+            None => self.start.get(&edge_index),
+            Some(_) => None,
+        }.or_else(|| self.goal.as_ref().and_then(|g| g.edges.get(&edge_index)));
+        share.map_or(edge_cost, |s| snap::partial_cost(edge_cost, *s))
+    }
+
+    fn turn_cost(&self, from: &Edge, to: &Edge) -> u32 {
+        if let Some(turn_costs) = self.params.turn_costs {
+            turn_costs.cost(self.graph.turn(from, to))
         } else {
-            self.graph.coordinates(from)
-                .distance(self.graph.coordinates(to)).round() as u32
+            0
         }
     }
+
+    fn path(&self, goal_edge_index: usize) -> Vec<&'a Edge> {
+        let mut path = vec![self.graph.edge(goal_edge_index)];
+        let mut index = goal_edge_index;
+        while let Some(prev_index) = self.settled[&index].1 {
+            path.push(self.graph.edge(prev_index));
+            index = prev_index;
+        }
+        path.reverse();
+        path
+    }
+
+    #[cfg(test)]
+    fn dijkstra(graph: &'a Graph, params: Params) -> Self {
+        let mut router = Self::new(graph, params);
+        router.use_heuristic = false;
+        router
+    }
+
+    fn heuristic(&self, from: usize) -> u32 {
+        let goal = match &self.goal {
+            Some(goal) if self.use_heuristic => goal,
+            _ => return 0,
+        };
+        let distance = self.graph.coordinates(from).distance(&goal.coordinates);
+        let estimate = if self.params.routing == Time {
+            // no edge can be traversed faster than with the maximum speed of the graph
+            let max_speed = match (self.params.transport, self.params.speed_model) {
+                (Car, _) => self.graph.max_speed.speed,
+                (Bike, SpeedModel::Constant) => BIKE_SPEED,
+                (Bike, SpeedModel::Terrain { .. }) => MAX_BIKE_SPEED,
+                (_, SpeedModel::Constant) => WALK_SPEED,
+                (_, SpeedModel::Terrain { .. }) => MAX_WALK_SPEED,
+            };
+            let ms = f64::from(max_speed) / 3.6;
+            (distance / ms).floor() as u32
+        } else {
+            // edge distances are rounded up
+            distance.floor() as u32
+        };
+
+        self.landmarks.map_or(estimate, |l| {
+            let bound = goal.nodes.iter()
+                .map(|n| l.lower_bound(from, *n))
+                .min()
+                .unwrap();
+            estimate.max(bound)
+        })
+    }
+}
+
+fn is_reverse(edge: &Edge, other: &Edge) -> bool {
+    edge.source_index == other.target_index && edge.target_index == other.source_index
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -125,7 +451,13 @@ impl PartialOrd for RouterNode {
 mod tests {
     use std::collections::BinaryHeap;
 
-    use crate::graph::router::RouterNode;
+    use crate::graph::fixtures;
+    use crate::graph::restriction::Restriction;
+    use crate::graph::router;
+    use crate::graph::router::options::{Params, PROFILES, Routing, Transport};
+    use crate::graph::router::{Router, RouterNode};
+    use crate::osm::Coordinates;
+    use crate::osm::restriction::Kind;
 
     #[test]
     fn min_priority_queue() {
@@ -144,4 +476,125 @@ mod tests {
         assert_eq!(queue.pop().unwrap().cost, 15);
         assert_eq!(queue.pop().unwrap().cost, 20);
     }
+
+    #[test]
+    fn same_route_as_dijkstra() {
+        let mut graph = fixtures::grid(12);
+        graph.select_landmarks();
+
+        for (transport, routing) in PROFILES.iter() {
+            let params = Params::new(*transport, *routing, false);
+            for (start, goal) in &[(0, 143), (11, 132), (70, 12), (143, 1), (64, 65)] {
+                let start = graph.coordinates(*start);
+                let goal = graph.coordinates(*goal);
+                let expected = Router::dijkstra(&graph, params.clone())
+                    .shortest_path(start, goal);
+                let actual = Router::new(&graph, params.clone())
+                    .shortest_path(start, goal);
+                // walking isn't allowed on all highways of the grid
+                let (expected, actual) = match (expected, actual) {
+                    (Ok(expected), Ok(actual)) => (expected, actual),
+                    (expected, actual) => {
+                        assert!(expected.is_err() && actual.is_err());
+                        continue;
+                    }
+                };
+
+                let cost = |edges: &[crate::graph::Edge]| edges.iter()
+                    .map(|e| e.cost(&params))
+                    .sum::<u32>();
+                // distances and travel times are rounded, so there may be several shortest routes
+                assert_eq!(cost(&expected.edges), cost(&actual.edges));
+            }
+        }
+    }
+
+    #[test]
+    fn goal_on_edge() {
+        // the goal lies right next to s on the edge towards t
+        let coordinates = [(48.0, 9.0), (48.0, 9.02), (48.001, 9.012)];
+        let edges = [(0, 1, "residential"), (2, 1, "residential"), (2, 0, "residential")];
+        let graph = fixtures::graph(&coordinates, &edges);
+        let start = graph.coordinates(2);
+        let goal = Coordinates::from(47.9999, 9.001);
+
+        for routing in &[Routing::Distance, Routing::Time] {
+            let params = Params::new(Transport::Car, *routing, false);
+            let expected = Router::dijkstra(&graph, params.clone()).shortest_path(start, &goal).unwrap();
+            let actual = Router::new(&graph, params).shortest_path(start, &goal).unwrap();
+            assert_eq!(expected.distance, actual.distance);
+            assert_eq!(actual.nodes[1].id, 0);
+        }
+    }
+
+    #[test]
+    fn turn_restrictions() {
+        // west, center, north, east and north-east node
+        let coordinates = [(48.0, 8.99), (48.0, 9.0), (48.01, 9.0), (48.0, 9.01), (48.01, 9.01)];
+        let edges = [
+            (0, 1, "residential"),
+            (1, 2, "residential"),
+            (1, 3, "residential"),
+            (3, 4, "residential"),
+            (4, 2, "residential"),
+        ];
+        let route = |restrictions: Vec<Restriction>, transport: Transport| {
+            let mut graph = fixtures::restricted_graph(&coordinates, &edges, restrictions);
+            graph.contract();
+            let params = Params::new(transport, Routing::Distance, false);
+            let start = graph.coordinates(0).clone();
+            let goal = graph.coordinates(2).clone();
+
+            let route = Router::new(&graph, params.clone())
+                .shortest_path(&start, &goal).unwrap();
+            let dispatched = router::shortest_path(&graph, &params, &start, &goal).unwrap();
+            assert_eq!(route.edges, dispatched.edges);
+            route.nodes.iter().map(|n| n.id).collect::<Vec<i64>>()
+        };
+        let restriction = |kind, to_index| vec![Restriction {
+            from_index: 0,
+            via_index: 1,
+            to_index,
+            kind,
+            transport: Transport::Car,
+        }];
+
+        assert_eq!(route(Vec::new(), Transport::Car), vec![0, 1, 2]);
+        assert_eq!(route(restriction(Kind::No, 2), Transport::Car), vec![0, 1, 3, 4, 2]);
+        assert_eq!(route(restriction(Kind::Only, 3), Transport::Car), vec![0, 1, 3, 4, 2]);
+        assert_eq!(route(restriction(Kind::Only, 2), Transport::Car), vec![0, 1, 2]);
+        assert_eq!(route(restriction(Kind::No, 2), Transport::Bike), vec![0, 1, 2]);
+
+        // the forbidden turn wins, so no turn is left at all
+        let conflicting = [restriction(Kind::Only, 2), restriction(Kind::No, 2)].concat();
+        let graph = fixtures::restricted_graph(&coordinates, &edges, conflicting);
+        assert!(!graph.is_turn_allowed(0, 1, 2, Transport::Car));
+        assert!(!graph.is_turn_allowed(0, 1, 3, Transport::Car));
+    }
+
+    #[test]
+    fn curvy() {
+        // straight road from west to east and a slightly longer detour over a winding road
+        let coordinates = [(48.0, 8.99), (48.0, 9.0), (48.0, 9.01), (48.002, 9.0)];
+        let edges = [
+            (0, 1, "secondary"),
+            (1, 2, "secondary"),
+            (0, 3, "tertiary"),
+            (3, 2, "tertiary"),
+        ];
+        let mut graph = fixtures::graph(&coordinates, &edges);
+        for edge in graph.edges.iter_mut().filter(|e| e.source_index == 3 || e.target_index == 3) {
+            edge.meta.curvature = 12;
+        }
+        let route = |routing| {
+            let params = Params::new(Transport::Car, routing, false);
+            let start = graph.coordinates(0);
+            let goal = graph.coordinates(2);
+            Router::new(&graph, params).shortest_path(start, goal).unwrap()
+                .nodes.iter().map(|n| n.id).collect::<Vec<i64>>()
+        };
+
+        assert_eq!(route(Routing::Distance), vec![0, 1, 2]);
+        assert_eq!(route(Routing::Curvy), vec![0, 3, 2]);
+    }
 }
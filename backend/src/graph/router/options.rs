@@ -1,18 +1,47 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 use strum_macros::EnumString;
 
+use crate::graph::router::options::Routing::{Distance, Time};
 use crate::graph::router::options::Transport::{All, Bike, BikeWalk, Car, CarBike, Walk};
-use crate::osm::Highway::{Cycleway, Footway, LivingStreet, Motorway, MotorwayLink, Path,
+use crate::osm::Highway::{Cycleway, Ferry, Footway, LivingStreet, Motorway, MotorwayLink, Path,
                           Pedestrian, Primary, PrimaryLink, Residential, Road, Secondary,
                           SecondaryLink, Service, Steps,
                           Tertiary, TertiaryLink, Track, Trunk, TrunkLink, Unclassified};
+use crate::graph::router::area::Area;
+use crate::graph::router::turn::Turn;
 use crate::osm::Highway;
 
-#[derive(Debug, Clone)]
+/// Profiles of default requests which are worth preprocessing for
+pub const PROFILES: [(Transport, Routing); 4] = [
+    (Car, Time),
+    (Car, Distance),
+    (Bike, Distance),
+    (Walk, Distance),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Params {
     pub transport: Transport,
     pub routing: Routing,
     pub avoid_unpaved: bool,
+    pub turn_costs: Option<TurnCosts>,
+    /// Weight of curviness against detours for curvy routing
+    pub curviness: f32,
+    /// Steepest climb in percent which is still allowed
+    pub max_grade: Option<u8>,
+    /// Additional cost share per 10% of climbing, 0 ignores the grade
+    pub grade_weight: f32,
+    /// Additional cost share of flat roads so round trips prefer steep ones, 0 ignores the grade
+    pub hilliness: f32,
+    /// Edges with any of these features are never used
+    pub avoid: HashSet<Avoid>,
+    /// Edges intersecting any of the areas are never used
+    pub avoid_areas: Vec<Area>,
+    /// How fast bikes and pedestrians are for time routing
+    pub speed_model: SpeedModel,
 }
 
 impl Params {
@@ -21,6 +50,79 @@ impl Params {
             transport,
             routing,
             avoid_unpaved,
+            turn_costs: None,
+            curviness: 1.0,
+            max_grade: None,
+            grade_weight: 0.0,
+            hilliness: 0.0,
+            avoid: HashSet::new(),
+            avoid_areas: Vec::new(),
+            speed_model: SpeedModel::Constant,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeedModel {
+    /// Same speed regardless of the terrain
+    #[default]
+    Constant,
+    /// Tobler's hiking function for walking and a power-based model for cycling
+    /// with the power of the rider in watts and the weight of rider and bike in kg
+    Terrain { power: f32, weight: f32 },
+}
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum Avoid {
+    Highway(Highway),
+    Toll,
+}
+
+impl FromStr for Avoid {
+    type Err = &'static str;
+
+    /// Either `toll` or a highway type like `motorway` or `ferry`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "toll" {
+            return Ok(Self::Toll);
+        }
+        Highway::from_str(s)
+            .map(Self::Highway)
+            .map_err(|_| "Unknown feature to avoid")
+    }
+}
+
+/// Penalties for turning at intersections,
+/// given in the unit of the routing i.e. seconds or meters
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TurnCosts {
+    pub left: u32,
+    pub right: u32,
+    pub u_turn: u32,
+}
+
+impl TurnCosts {
+    /// Left and right turns are scaled by their deflection up to 90 degrees
+    pub fn cost(self, turn: Turn) -> u32 {
+        let scaled = |penalty: u32, deflection: f32|
+            (penalty as f32 * (deflection / 90.0).min(1.0)).round() as u32;
+        match turn {
+            Turn::Straight => 0,
+            Turn::Left(deflection) => scaled(self.left, deflection),
+            Turn::Right(deflection) => scaled(self.right, deflection),
+            Turn::Around => self.u_turn,
+        }
+    }
+}
+
+impl Default for TurnCosts {
+    fn default() -> Self {
+        Self {
+            left: 10,
+            right: 5,
+            u_turn: 60,
         }
     }
 }
@@ -40,7 +142,7 @@ pub enum Transport {
 impl Transport {
     pub fn from(highway: Highway) -> Self {
         match highway {
-            Residential | Tertiary | Unclassified | Service | LivingStreet | TertiaryLink => All,
+            Residential | Tertiary | Unclassified | Service | LivingStreet | TertiaryLink | Ferry => All,
             Secondary | SecondaryLink | Primary | PrimaryLink => CarBike,
             Track | Road => BikeWalk,
             Motorway | MotorwayLink | Trunk | TrunkLink => Car,
@@ -61,4 +163,6 @@ impl Transport {
 pub enum Routing {
     Time,
     Distance,
+    /// Prefers curvy roads
+    Curvy,
 }
\ No newline at end of file
@@ -0,0 +1,188 @@
+use crate::graph::{Edge, Graph};
+use crate::graph::router::instruction::Maneuver;
+use crate::graph::router::options::Params;
+use crate::graph::router::options::Routing::Curvy;
+use crate::graph::router::route::{Route, RouteBuilder};
+use crate::graph::router::Router;
+use crate::graph::router::snap::Snap;
+use crate::osm::Coordinates;
+
+/// Roads are assumed to be this much longer than the beeline
+const DETOUR_FACTOR: f64 = 1.3;
+/// Cost factor of edges which are already part of the loop
+const REUSE_PENALTY: f32 = 5.0;
+/// Number of directions in which a loop is tried
+const DIRECTIONS: usize = 8;
+
+/// Closed route from the start back to it with roughly the given distance in meters.
+/// The loop runs over two waypoints which form an equilateral triangle with the start,
+/// it is tried in several directions and the one closest to the distance is taken.
+/// As the length of the loop is given anyway, its legs are always routed curvy
+/// while the curviness, grade and hilliness preferences of the params still apply.
+pub fn round_trip(graph: &Graph, params: &Params, start: &Coordinates, distance: u32)
+                  -> Result<Route, &'static str> {
+    let mut params = params.clone();
+    params.routing = Curvy;
+    let start = graph.snap(start, &params)?;
+    // the perimeter of an equilateral triangle is 3 * sqrt(3) times its circumradius
+    let radius = f64::from(distance) / DETOUR_FACTOR / (3.0 * 3f64.sqrt());
+
+    let mut best: Option<(u32, Route)> = None;
+    for i in 0..DIRECTIONS {
+        let bearing = i as f64 * 360.0 / DIRECTIONS as f64;
+        let center = start.coordinates.destination(bearing, radius);
+        let waypoints = [
+            center.destination(bearing + 180.0 + 120.0, radius),
+            center.destination(bearing + 180.0 - 120.0, radius),
+        ];
+
+        let route = if let Ok(route) = loop_route(graph, &params, &start, &waypoints) {
+            route
+        } else {
+            continue;
+        };
+        let deviation = (i64::from(route.distance) - i64::from(distance)).unsigned_abs() as u32;
+        if best.as_ref().is_none_or(|(d, _)| deviation < *d) {
+            best = Some((deviation, route));
+        }
+    }
+
+    best.map(|(_, route)| route).ok_or("No round trip found")
+}
+
+/// Route from the start over the waypoints back to the start, edges of previous legs are
+/// penalized and the waypoints are passed without turning so the loop doesn't go back the same way
+fn loop_route(graph: &Graph, params: &Params, start: &Snap, waypoints: &[Coordinates])
+              -> Result<Route, &'static str> {
+    let mut stops = vec![start.clone()];
+    for waypoint in waypoints {
+        let snap = graph.snap(waypoint, params)?;
+        if stops.iter().any(|s| s.coordinates == snap.coordinates) {
+            return Err("Waypoints are too close to each other");
+        }
+        stops.push(snap);
+    }
+    stops.push(start.clone());
+
+    let mut router = Router::new(graph, params.clone());
+    let builder = RouteBuilder::new(graph, params);
+    let mut parts = Vec::with_capacity(stops.len() - 1);
+    let mut arrival: Option<&Edge> = None;
+    for leg in stops.windows(2) {
+        let path = match arrival {
+            Some(arrival) => router.search_onward(arrival, &leg[0], &leg[1])?,
+            None => router.search_snapped(&leg[0], &leg[1])?,
+        };
+        for edge in &path {
+            for index in edge_indices(graph, edge) {
+                router.penalize(index, REUSE_PENALTY);
+            }
+        }
+        arrival = path.last().copied();
+        parts.push(builder.build_snapped(&path, &leg[0], &leg[1]));
+    }
+    let mut route = Route::concat(parts).unwrap();
+    // the waypoints are only there to shape the loop
+    route.instructions.retain(|i| i.maneuver != Maneuver::Waypoint);
+    Ok(route)
+}
+
+/// Indices of the edge and of its reverse edge if there is one
+fn edge_indices(graph: &Graph, edge: &Edge) -> Vec<usize> {
+    graph.edges_between(edge.source_index, edge.target_index)
+        .chain(graph.edges_between(edge.target_index, edge.source_index))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::graph::{Edge, Graph};
+    use crate::graph::fixtures;
+    use crate::graph::router::instruction::Maneuver;
+    use crate::graph::router::options::{Params, Routing, Transport};
+    use crate::graph::router::round_trip::round_trip;
+    use crate::osm::Coordinates;
+
+    #[test]
+    fn closed_loop() {
+        let graph = fixtures::grid(12);
+        let params = Params::new(Transport::Car, Routing::Curvy, false);
+        let start = graph.coordinates(66).clone();
+
+        let route = round_trip(&graph, &params, &start, 3_000).unwrap();
+        assert_eq!(route.nodes.first().unwrap().id, 66);
+        assert_eq!(route.nodes.last().unwrap().id, 66);
+        assert!(route.instructions.iter().all(|i| i.maneuver != Maneuver::Waypoint));
+        assert!(route.distance > 2_000 && route.distance < 4_000);
+
+        // edges with a waypoint are split into two parts, but no road is used twice
+        let mut roads: HashMap<_, u32> = HashMap::new();
+        for edge in &route.edges {
+            let road = (edge.source_index.min(edge.target_index), edge.source_index.max(edge.target_index));
+            *roads.entry(road).or_default() += edge.distance();
+        }
+        for ((source, target), distance) in roads {
+            let full = graph.edges_between(source, target).next().unwrap();
+            assert!(distance <= graph.edge(full).distance() + 1);
+        }
+    }
+
+    #[test]
+    fn starts_on_edge() {
+        let graph = fixtures::grid(12);
+        let params = Params::new(Transport::Car, Routing::Curvy, false);
+        let (from, to) = (graph.coordinates(66), graph.coordinates(67));
+        let start = Coordinates::from((from.lat() + to.lat()) / 2.0, (from.lon() + to.lon()) / 2.0);
+
+        let route = round_trip(&graph, &params, &start, 3_000).unwrap();
+        let (first, last) = (route.nodes.first().unwrap(), route.nodes.last().unwrap());
+        assert_eq!(first.coordinates, last.coordinates);
+        assert!(first.coordinates.distance(&start) < 10.0);
+    }
+
+    #[test]
+    fn prefers_curvy_roads() {
+        let start = fixtures::grid(12).coordinates(66).clone();
+        let route = |graph: &Graph, routing| {
+            let params = Params::new(Transport::Car, routing, false);
+            round_trip(graph, &params, &start, 3_000).unwrap().edges
+        };
+        let road = |edge: &Edge| (edge.source_index.min(edge.target_index), edge.source_index.max(edge.target_index));
+
+        // all roads which the loop doesn't take yet are winding
+        let mut graph = fixtures::grid(12);
+        let plain: HashSet<_> = route(&graph, Routing::Distance).iter().map(road).collect();
+        for edge in graph.edges.iter_mut().filter(|e| !plain.contains(&road(e))) {
+            edge.meta.curvature = 50;
+        }
+
+        // curvy routing is used regardless of the requested routing
+        let curvy = route(&graph, Routing::Distance);
+        assert_eq!(curvy, route(&graph, Routing::Curvy));
+        assert!(curvy.iter().any(|e| e.meta.curvature > 0));
+    }
+
+    #[test]
+    fn prefers_steep_roads() {
+        let start = fixtures::grid(12).coordinates(66).clone();
+        let route = |graph: &Graph, hilliness| {
+            let mut params = Params::new(Transport::Car, Routing::Curvy, false);
+            params.hilliness = hilliness;
+            round_trip(graph, &params, &start, 3_000).unwrap().edges
+        };
+        let road = |edge: &Edge| (edge.source_index.min(edge.target_index), edge.source_index.max(edge.target_index));
+
+        // all roads which the loop doesn't take yet are steep
+        let mut graph = fixtures::grid(12);
+        let plain = route(&graph, 0.0);
+        let roads: HashSet<_> = plain.iter().map(road).collect();
+        for edge in graph.edges.iter_mut().filter(|e| !roads.contains(&road(e))) {
+            edge.meta.grade = Some(if edge.source_index < edge.target_index { 80 } else { -80 });
+        }
+
+        assert_eq!(route(&graph, 0.0), plain);
+        assert!(route(&graph, 1.0).iter().any(|e| e.meta.grade.is_some()));
+    }
+}
@@ -3,49 +3,67 @@ use serde::{Deserialize, Serialize};
 use crate::graph::edge::Edge;
 use crate::graph::Graph;
 use crate::graph::node::Node;
-use crate::graph::router::options::Transport;
+use crate::graph::router::climb::{self, Climb};
+use crate::graph::router::instruction::{self, Instruction, Maneuver};
+use crate::graph::router::options::Params;
+use crate::graph::router::snap::{self, Snap};
 use crate::osm::Coordinates;
 
-pub struct RouteBuilder<'a, 'b> {
+pub struct RouteBuilder<'a> {
     graph: &'a Graph,
-    prev: &'b [Option<&'a Edge>],
-    transport: Transport,
+    params: &'a Params,
 }
 
-impl<'a, 'b> RouteBuilder<'a, 'b> {
-    pub fn new(graph: &'a Graph, prev: &'b [Option<&'a Edge>], transport: Transport) -> Self {
+impl<'a> RouteBuilder<'a> {
+    pub fn new(graph: &'a Graph, params: &'a Params) -> Self {
         Self {
             graph,
-            prev,
-            transport,
+            params,
         }
     }
 
-    pub fn build(&self, start_index: usize, goal_index: usize) -> Route {
+    /// Builds the route along the given consecutive edges
+    pub fn build(&self, path: &[&Edge]) -> Route {
+        self.build_between(path, None, None)
+    }
+
+    /// Builds the route between two snapped points, the first and last edge are only
+    /// traversed partially if the points lie between the nodes of the edges
+    pub fn build_snapped(&self, path: &[&Edge], start: &Snap, goal: &Snap) -> Route {
+        let edges = snap::trim(self.graph, path, start, goal);
+        let edges: Vec<&Edge> = edges.iter().collect();
+        let virtual_node = |snap: &Snap| match snap.node_index(self.graph) {
+            Some(_) => None,
+            None => Some(snap.node(self.graph)),
+        };
+        self.build_between(&edges, virtual_node(start), virtual_node(goal))
+    }
+
+    /// Virtual start and goal nodes replace the first and last node of the path
+    fn build_between(&self, path: &[&Edge], start: Option<Node>, goal: Option<Node>) -> Route {
         let mut route = Route::new();
+        let goal_index = path.last().unwrap().target_index;
 
-        let mut edge = self.prev[goal_index].unwrap();
-        loop {
+        route.nodes.push(start.unwrap_or_else(|| self.graph.node(path[0].source_index).clone()));
+        for edge in path {
             route.nodes.push(self.graph.node(edge.target_index).clone());
-            route.edges.push(edge.clone());
+            route.edges.push((*edge).clone());
             route.distance += edge.distance();
-            route.time += edge.time(self.transport);
+            route.time += edge.time(self.params);
             if self.graph.edges(edge.target_index).len() > 2 &&
                 edge.target_index != goal_index {
                 // - in  and outgoing edge
                 route.intersections += self.graph.edges(edge.target_index).len() - 2;
             }
+        }
 
-            edge = self.prev[edge.source_index].unwrap();
-            if edge.source_index == start_index {
-                route.nodes.push(self.graph.node(edge.source_index).clone());
-                break;
-            }
+        if let Some(goal) = goal {
+            *route.nodes.last_mut().unwrap() = goal;
         }
 
-        route.nodes.reverse();
-        route.edges.reverse();
         route.calc_curvature();
+        route.calc_elevation();
+        route.instructions = instruction::instructions(self.graph, path, self.params);
         route
     }
 }
@@ -58,6 +76,14 @@ pub struct Route {
     pub distance: u32,
     pub intersections: usize,
     pub curvature: Curvature,
+    /// Total elevation gain in meters
+    pub ascent: f32,
+    /// Total elevation loss in meters
+    pub descent: f32,
+    pub min_elevation: Option<f32>,
+    pub max_elevation: Option<f32>,
+    pub climbs: Vec<Climb>,
+    pub instructions: Vec<Instruction>,
 }
 
 impl Route {
@@ -69,10 +95,41 @@ impl Route {
             distance: 0,
             intersections: 0,
             curvature: Curvature::new(),
+            ascent: 0.0,
+            descent: 0.0,
+            min_elevation: None,
+            max_elevation: None,
+            climbs: Vec::new(),
+            instructions: Vec::new(),
+        }
+    }
+
+    /// Joins the routes one after another, climbs are detected once
+    /// since they may continue over the stops between the routes
+    pub fn concat(routes: Vec<Route>) -> Option<Route> {
+        let mut routes = routes.into_iter();
+        let mut route = routes.next()?;
+        for other in routes {
+            route.append(other);
         }
+        route.climbs = climb::detect(&route.nodes);
+        Some(route)
     }
 
-    pub fn merge(&mut self, mut other: Route) {
+    fn append(&mut self, mut other: Route) {
+        // the arrival becomes a stop and instructions refer to the nodes of the merged route
+        let offset = self.nodes.len() - 1;
+        if self.instructions.last().is_some_and(|i| i.maneuver == Maneuver::Arrive) {
+            self.instructions.pop();
+        }
+        for (i, instruction) in other.instructions.iter_mut().enumerate() {
+            if i == 0 && instruction.maneuver == Maneuver::Depart {
+                instruction.set_maneuver(Maneuver::Waypoint);
+            }
+            instruction.node += offset;
+        }
+        self.instructions.extend(other.instructions);
+
         // already included as the goal of previous route
         other.nodes.remove(0);
         self.nodes.extend(other.nodes);
@@ -82,9 +139,34 @@ impl Route {
         self.intersections += other.intersections;
         self.curvature.radii.extend(other.curvature.radii);
         self.curvature.score += other.curvature.score;
+        self.ascent += other.ascent;
+        self.descent += other.descent;
+        self.min_elevation = combine(self.min_elevation, other.min_elevation, f32::min);
+        self.max_elevation = combine(self.max_elevation, other.max_elevation, f32::max);
+    }
+
+    fn calc_elevation(&mut self) {
+        let elevations: Vec<f32> = self.nodes.iter()
+            .filter_map(|n| n.meta.elevation)
+            .collect();
+        for pair in elevations.windows(2) {
+            let rise = pair[1] - pair[0];
+            if rise > 0.0 {
+                self.ascent += rise;
+            } else {
+                self.descent -= rise;
+            }
+        }
+        self.min_elevation = elevations.iter().copied().reduce(f32::min);
+        self.max_elevation = elevations.iter().copied().reduce(f32::max);
+        self.climbs = climb::detect(&self.nodes);
     }
 
     fn calc_curvature(&mut self) {
+        if self.nodes.len() < 3 {
+            self.curvature.radii = vec![Radius(None); self.nodes.len()];
+            return;
+        }
         self.curvature.radii.push(Radius::gamma(
             &self.nodes[0].coordinates,
             &self.nodes[1].coordinates,
@@ -108,6 +190,14 @@ impl Route {
     }
 }
 
+/// Combines both values if there are two, otherwise takes the one there is
+fn combine(a: Option<f32>, b: Option<f32>, f: fn(f32, f32) -> f32) -> Option<f32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(f(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Curvature {
     pub radii: Vec<Radius>,
@@ -127,7 +217,8 @@ impl Curvature {
 pub struct Radius(Option<f32>);
 
 impl Radius {
-    fn gamma(c1: &Coordinates, c2: &Coordinates, c3: &Coordinates) -> Self {
+    /// Angle at c2 between c1 and c3, i.e. 180 degrees if they are in line
+    pub fn gamma(c1: &Coordinates, c2: &Coordinates, c3: &Coordinates) -> Self {
         let (a, b, c) = Self::sides(c1, c2, c3);
         let radians = ((a.powi(2) + b.powi(2) - c.powi(2)) / (2.0 * a * b))
             .acos();
@@ -143,14 +234,30 @@ impl Radius {
         }
     }
 
+    /// Radius in meters of the circle through the three points, if they aren't in line
+    pub fn meters(c1: &Coordinates, c2: &Coordinates, c3: &Coordinates) -> Option<f32> {
+        let (_, _, c) = Self::sides(c1, c2, c3);
+        let gamma = Self::gamma(c1, c2, c3).degrees()?;
+        let sin = gamma.to_radians().sin();
+        if sin > 0.0 {
+            Some(c / (2.0 * sin))
+        } else {
+            None
+        }
+    }
+
+    pub fn degrees(self) -> Option<f32> {
+        self.0
+    }
+
     fn sides(c1: &Coordinates, c2: &Coordinates, c3: &Coordinates) -> (f32, f32, f32) {
-        let a = c2.distance(&c3) as f32;
-        let b = c1.distance(&c2) as f32;
-        let c = c1.distance(&c3) as f32;
+        let a = c2.distance(c3) as f32;
+        let b = c1.distance(c2) as f32;
+        let c = c1.distance(c3) as f32;
         (a, b, c)
     }
 
-    fn score(self) -> f32 {
+    pub fn score(self) -> f32 {
         if self.0.is_none() {
             return 0.0;
         }
@@ -167,3 +274,52 @@ impl Radius {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::fixtures;
+    use crate::graph::router::options::{Params, Routing, Transport};
+    use crate::graph::router::route::{Radius, Route, RouteBuilder};
+    use crate::osm::Coordinates;
+
+    #[test]
+    fn merge_elevation() {
+        // 1.5 km climb at 8% which is split by a stop, followed by a descent
+        let mut coordinates = Vec::new();
+        for i in 0..20 {
+            let elevation = if i <= 15 { 100.0 + i as f32 * 8.0 } else { 220.0 - (i - 15) as f32 * 10.0 };
+            coordinates.push((48.0 + i as f64 * 0.0009, 9.0, elevation));
+        }
+        let edges: Vec<(usize, usize, &str)> = (0..19).map(|i| (i, i + 1, "tertiary")).collect();
+        let graph = fixtures::hilly_graph(&coordinates, &edges);
+        let path: Vec<_> = (0..19)
+            .map(|i| graph.edges(i).iter().find(|e| e.target_index == i + 1).unwrap())
+            .collect();
+        let params = Params::new(Transport::Bike, Routing::Distance, false);
+        let builder = RouteBuilder::new(&graph, &params);
+
+        let whole = builder.build(&path);
+        let merged = Route::concat(vec![builder.build(&path[..8]), builder.build(&path[8..])]).unwrap();
+        assert!((whole.ascent - 120.0).abs() < 0.1);
+        assert!((whole.descent - 40.0).abs() < 0.1);
+        assert_eq!(merged.ascent, whole.ascent);
+        assert_eq!(merged.descent, whole.descent);
+        assert_eq!(merged.min_elevation, Some(100.0));
+        assert_eq!(merged.max_elevation, Some(220.0));
+        assert_eq!(whole.climbs.len(), 1);
+        assert_eq!(merged.climbs, whole.climbs);
+    }
+
+    #[test]
+    fn radius() {
+        let center = Coordinates::from(48.0, 9.0);
+        let on_circle: Vec<Coordinates> = [0.0, 30.0, 60.0].iter()
+            .map(|bearing| center.destination(*bearing, 100.0))
+            .collect();
+        let radius = Radius::meters(&on_circle[0], &on_circle[1], &on_circle[2]).unwrap();
+        assert!((radius - 100.0).abs() < 1.0);
+
+        let (a, b, c) = (Coordinates::from(48.0, 9.0), Coordinates::from(48.0, 9.001), Coordinates::from(48.0, 9.002));
+        assert!(Radius::meters(&a, &b, &c).is_none_or(|r| r > 10_000.0));
+    }
+}
@@ -0,0 +1,270 @@
+use crate::graph::{Edge, Graph};
+use crate::graph::node::{self, Node};
+use crate::graph::router::options::Params;
+use crate::osm::Coordinates;
+
+/// Fractions this close to either end of an edge are considered to be on the node
+const NODE_TOLERANCE: f64 = 1e-6;
+/// Points projected this close in meters to a node are snapped onto the node
+/// instead of leaving a tiny part of an edge next to it
+const NODE_RADIUS: f64 = 2.0;
+/// Points farther away in meters from any road are not on the map
+const MAX_DISTANCE: f64 = 1000.0;
+
+/// Point projected onto the closest relevant edge
+#[derive(Debug, Clone)]
+pub struct Snap {
+    pub edge_index: usize,
+    /// Share of the edge from its source to the projected point
+    pub fraction: f64,
+    pub coordinates: Coordinates,
+}
+
+impl Graph {
+    /// Projects the point onto the closest relevant edge
+    pub fn snap(&self, coords: &Coordinates, params: &Params) -> Result<Snap, &'static str> {
+        let (edge_index, _, fraction) = self.edges_within(coords, params, MAX_DISTANCE).into_iter().next()
+            .ok_or("Couldn't locate point on map")?;
+        let edge = self.edge(edge_index);
+        let projected = interpolate(self.coordinates(edge.source_index), self.coordinates(edge.target_index), fraction);
+        let node_edge = self.nearest_node(&projected, params)
+            .filter(|(_, distance)| *distance <= NODE_RADIUS)
+            .and_then(|(i, _)| self.edge_indices(i).find(|e| self.is_usable(*e, params)));
+        if let Some(edge_index) = node_edge {
+            return Ok(Snap {
+                edge_index,
+                fraction: 0.0,
+                coordinates: self.coordinates(self.edge(edge_index).source_index).clone(),
+            });
+        }
+
+        let (fraction, coordinates) = if fraction < NODE_TOLERANCE {
+            (0.0, self.coordinates(edge.source_index).clone())
+        } else if fraction > 1.0 - NODE_TOLERANCE {
+            (1.0, self.coordinates(edge.target_index).clone())
+        } else {
+            (fraction, projected)
+        };
+        Ok(Snap {
+            edge_index,
+            fraction,
+            coordinates,
+        })
+    }
+}
+
+impl Snap {
+    /// The node the point lies on, if it isn't between the nodes of the edge
+    pub fn node_index(&self, graph: &Graph) -> Option<usize> {
+        let edge = graph.edge(self.edge_index);
+        if self.fraction == 0.0 {
+            Some(edge.source_index)
+        } else if self.fraction == 1.0 {
+            Some(edge.target_index)
+        } else {
+            None
+        }
+    }
+
+    /// The edge and its reverse edges with the share from their source to the point
+    pub fn edges(&self, graph: &Graph) -> Vec<(usize, f64)> {
+        let edge = graph.edge(self.edge_index);
+        let mut edges = vec![(self.edge_index, self.fraction)];
+        edges.extend(graph.edges_between(edge.target_index, edge.source_index)
+            .map(|i| (i, 1.0 - self.fraction)));
+        edges
+    }
+
+    /// Share from the source of the edge to the point if the point lies on it
+    pub fn fraction(&self, graph: &Graph, edge: &Edge) -> Option<f64> {
+        let snapped = graph.edge(self.edge_index);
+        if edge.source_index == snapped.source_index && edge.target_index == snapped.target_index {
+            Some(self.fraction)
+        } else if edge.source_index == snapped.target_index && edge.target_index == snapped.source_index {
+            Some(1.0 - self.fraction)
+        } else {
+            None
+        }
+    }
+
+    /// Virtual node at the point, it has no OSM id
+    pub fn node(&self, graph: &Graph) -> Node {
+        let edge = graph.edge(self.edge_index);
+        let source = graph.node(edge.source_index);
+        let target = graph.node(edge.target_index);
+        let elevation = match (source.meta.elevation, target.meta.elevation) {
+            (Some(s), Some(t)) => Some(s + (t - s) * self.fraction as f32),
+            _ => None
+        };
+        Node::new(-1, self.coordinates.clone(), node::Meta::new(elevation))
+    }
+}
+
+/// Index of the edge which leads from the start directly to the goal,
+/// if both points lie on the same edge in the right order
+pub fn direct(graph: &Graph, params: &Params, start: &Snap, goal: &Snap) -> Option<usize> {
+    if start.node_index(graph).is_some() || goal.node_index(graph).is_some() {
+        return None;
+    }
+    start.edges(graph).into_iter()
+        .filter(|(i, _)| graph.edge(*i).is_relevant(params))
+        .find(|(i, fraction)| goal.fraction(graph, graph.edge(*i))
+            .is_some_and(|f| f >= *fraction))
+        .map(|(i, _)| i)
+}
+
+/// Copy of the path whose first and last edge are shortened to the parts
+/// between the points, unless the points lie on a node
+pub fn trim(graph: &Graph, path: &[&Edge], start: &Snap, goal: &Snap) -> Vec<Edge> {
+    let mut edges: Vec<Edge> = path.iter().map(|e| (*e).clone()).collect();
+    if path.is_empty() {
+        return edges;
+    }
+    let from = start.node_index(graph)
+        .map_or_else(|| start.fraction(graph, path[0]), |_| None);
+    let to = goal.node_index(graph)
+        .map_or_else(|| goal.fraction(graph, path[path.len() - 1]), |_| None);
+
+    let last = edges.len() - 1;
+    if last == 0 {
+        edges[0] = partial(path[0], to.unwrap_or(1.0) - from.unwrap_or(0.0));
+    } else {
+        if let Some(from) = from {
+            edges[0] = partial(path[0], 1.0 - from);
+        }
+        if let Some(to) = to {
+            edges[last] = partial(path[last], to);
+        }
+    }
+    edges
+}
+
+/// Cost of traversing only the share of the edge, rounded up like the distances of edges
+pub fn partial_cost(cost: u32, share: f64) -> u32 {
+    (f64::from(cost) * share).ceil() as u32
+}
+
+/// Copy of the edge whose distance is reduced to the share
+pub fn partial(edge: &Edge, share: f64) -> Edge {
+    let mut partial = edge.clone();
+    partial.distance = Some((f64::from(edge.distance()) * share).ceil() as u32);
+    partial
+}
+
+/// Distance of the point to the segment and the fraction of the segment
+/// up to the projected point, using an equirectangular projection
+pub fn project(point: &Coordinates, from: &Coordinates, to: &Coordinates) -> (f64, f64) {
+    let scale = point.lat().to_radians().cos();
+    let xy = |c: &Coordinates| ((c.lon() - point.lon()) * scale, c.lat() - point.lat());
+    let (x1, y1) = xy(from);
+    let (x2, y2) = xy(to);
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let length = dx * dx + dy * dy;
+    let fraction = if length > 0.0 {
+        (-(x1 * dx + y1 * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (interpolate(from, to, fraction).distance(point), fraction)
+}
+
+pub fn interpolate(from: &Coordinates, to: &Coordinates, fraction: f64) -> Coordinates {
+    Coordinates::from(
+        from.lat() + fraction * (to.lat() - from.lat()),
+        from.lon() + fraction * (to.lon() - from.lon()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::fixtures;
+    use crate::graph::router;
+    use crate::graph::router::options::{Params, Routing, Transport};
+    use crate::osm::Coordinates;
+
+    #[test]
+    fn long_edge() {
+        // long road from west to east with a side road ending close to its middle
+        let coordinates = [(48.0, 9.0), (48.0, 9.04), (48.004, 9.02), (48.01, 9.02)];
+        let edges = [
+            (0, 1, "secondary"),
+            (2, 3, "residential"),
+            (3, 1, "residential"),
+        ];
+        let mut graph = fixtures::graph(&coordinates, &edges);
+        let params = Params::new(Transport::Car, Routing::Distance, false);
+        let length = graph.coordinates(0).distance(graph.coordinates(1));
+        let middle = Coordinates::from(48.0005, 9.02);
+        let quarter = Coordinates::from(47.9995, 9.01);
+
+        let snap = graph.snap(&middle, &params).unwrap();
+        let edge = graph.edge(snap.edge_index);
+        assert_eq!(edge.source_index + edge.target_index, 1);
+        assert!((snap.fraction - 0.5).abs() < 0.01);
+        assert!(snap.coordinates.distance(&middle) < 60.0);
+
+        graph.contract();
+        for _ in 0..2 {
+            let route = router::shortest_path(&graph, &params, &middle, graph.coordinates(1)).unwrap();
+            assert_eq!(route.nodes.first().unwrap().id, -1);
+            assert_eq!(route.nodes.last().unwrap().id, 1);
+            assert!((f64::from(route.distance) - length / 2.0).abs() < 20.0);
+
+            // both points on the same edge, in both directions
+            let route = router::shortest_path(&graph, &params, &quarter, &middle).unwrap();
+            assert_eq!(route.edges.len(), 1);
+            assert!((f64::from(route.distance) - length / 4.0).abs() < 20.0);
+            let route = router::shortest_path(&graph, &params, &middle, &quarter).unwrap();
+            assert_eq!(route.edges.len(), 1);
+            assert!((f64::from(route.distance) - length / 4.0).abs() < 20.0);
+            assert_eq!(route.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![-1, -1]);
+
+            // without the hierarchy on the second run
+            graph.hierarchies.clear();
+        }
+    }
+
+    #[test]
+    fn across_nodes() {
+        let coordinates = [(48.0, 9.0), (48.0, 9.01), (48.0, 9.02)];
+        let edges = [(0, 1, "residential"), (1, 2, "residential")];
+        let graph = fixtures::graph(&coordinates, &edges);
+        let params = Params::new(Transport::Car, Routing::Distance, false);
+        let start = Coordinates::from(48.0001, 9.005);
+        let goal = Coordinates::from(47.9999, 9.015);
+
+        let route = router::shortest_path(&graph, &params, &start, &goal).unwrap();
+        assert_eq!(route.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![-1, 1, -1]);
+        assert_eq!(route.distance, route.edges.iter().map(|e| e.distance()).sum::<u32>());
+        let half = f64::from(graph.edge(0).distance()) / 2.0;
+        assert!((f64::from(route.edges[0].distance()) - half).abs() <= 1.0);
+        assert!((f64::from(route.edges[1].distance()) - half).abs() <= 1.0);
+        assert!(route.nodes[0].coordinates.distance(&start) < 15.0);
+    }
+
+    #[test]
+    fn next_to_node() {
+        let coordinates = [(48.0, 9.0), (48.0, 9.01), (48.01, 9.01)];
+        let edges = [(0, 1, "residential"), (1, 2, "residential")];
+        let graph = fixtures::graph(&coordinates, &edges);
+        let params = Params::new(Transport::Car, Routing::Distance, false);
+
+        // a meter before the corner
+        let snap = graph.snap(&Coordinates::from(48.0, 9.009_99), &params).unwrap();
+        assert_eq!(snap.node_index(&graph), Some(1));
+        assert_eq!(&snap.coordinates, graph.coordinates(1));
+        let snap = graph.snap(&Coordinates::from(48.0, 9.009_9), &params).unwrap();
+        assert_eq!(snap.node_index(&graph), None);
+    }
+
+    #[test]
+    fn off_the_map() {
+        let coordinates = [(48.0, 9.0), (48.0, 9.01)];
+        let graph = fixtures::graph(&coordinates, &[(0, 1, "residential")]);
+        let params = Params::new(Transport::Car, Routing::Distance, false);
+
+        assert!(graph.snap(&Coordinates::from(48.005, 9.005), &params).is_ok());
+        assert_eq!(graph.snap(&Coordinates::from(48.02, 9.005), &params).unwrap_err(),
+                   "Couldn't locate point on map");
+    }
+}
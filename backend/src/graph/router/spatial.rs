@@ -0,0 +1,47 @@
+use crate::graph::Graph;
+use crate::graph::router::options::Params;
+use crate::graph::router::snap::project;
+use crate::graph::rtree::{Rect, RTree};
+use crate::graph::{Edge, Node};
+use crate::osm::Coordinates;
+
+pub fn node_tree(nodes: &[Node]) -> RTree {
+    RTree::new(nodes.iter()
+        .map(|n| Rect::new(&n.coordinates, &n.coordinates))
+        .collect())
+}
+
+pub fn edge_tree(nodes: &[Node], edges: &[Edge]) -> RTree {
+    RTree::new(edges.iter()
+        .map(|e| Rect::new(&nodes[e.source_index].coordinates, &nodes[e.target_index].coordinates))
+        .collect())
+}
+
+impl Graph {
+    /// Closest node with an edge which can be used, together with its distance in meters
+    pub fn nearest_node(&self, coords: &Coordinates, params: &Params) -> Option<(usize, f64)> {
+        self.node_tree.nearest(coords, |i| self.coordinates(i).distance(coords))
+            .find(|(i, _)| self.edge_indices(*i).any(|e| self.is_usable(e, params)))
+    }
+
+    /// Relevant edges outside the areas to avoid whose segment lies within the distance in meters
+    /// of the point by increasing distance, together with the distance and the fraction of the edge
+    /// up to the closest point
+    pub fn edges_within(&self, coords: &Coordinates, params: &Params, meters: f64) -> Vec<(usize, f64, f64)> {
+        let segment = |i: usize| {
+            let edge = self.edge(i);
+            project(coords, self.coordinates(edge.source_index), self.coordinates(edge.target_index))
+        };
+        self.edge_tree.within(coords, meters, |i| segment(i).0).into_iter()
+            .filter(|(i, _)| self.is_usable(*i, params))
+            .map(|(i, distance)| (i, distance, segment(i).1))
+            .collect()
+    }
+
+    /// Whether the edge is relevant and outside the areas to avoid
+    pub fn is_usable(&self, edge_index: usize, params: &Params) -> bool {
+        let edge = self.edge(edge_index);
+        let (from, to) = (self.coordinates(edge.source_index), self.coordinates(edge.target_index));
+        edge.is_relevant(params) && !params.avoid_areas.iter().any(|a| a.intersects(from, to))
+    }
+}
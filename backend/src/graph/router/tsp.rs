@@ -0,0 +1,280 @@
+use crate::graph::Graph;
+use crate::graph::router::matrix;
+use crate::graph::router::options::Params;
+use crate::graph::router::options::Routing::Time;
+use crate::osm::Coordinates;
+
+/// Stop counts up to which the optimal order is found via dynamic programming
+const EXACT_LIMIT: usize = 10;
+/// Longest sequence of stops which is moved at once by Or-opt
+const MAX_SEGMENT: usize = 3;
+
+/// Order of the stops which minimizes the total travel time for time routing and the total
+/// distance otherwise. The first and last stop keep their position if requested.
+pub fn optimize(graph: &Graph, params: &Params, stops: &[Coordinates], keep_first: bool, keep_last: bool)
+                -> Result<Vec<usize>, &'static str> {
+    let matrix = matrix::matrix(graph, params, stops, stops)?;
+    let costs = if params.routing == Time { matrix.times } else { matrix.distances };
+    order(&costs, keep_first, keep_last)
+}
+
+/// Exact for few stops, otherwise nearest neighbor improved by 2-opt and Or-opt
+fn order(costs: &[Vec<Option<u32>>], keep_first: bool, keep_last: bool) -> Result<Vec<usize>, &'static str> {
+    let order = if costs.len() <= EXACT_LIMIT {
+        exact(costs, keep_first, keep_last)
+    } else {
+        let mut order = nearest_neighbor(costs, keep_first, keep_last);
+        improve(costs, &mut order, keep_first, keep_last);
+        order
+    };
+    if total(costs, &order).is_none() {
+        return Err("No order of the stops is reachable");
+    }
+    Ok(order)
+}
+
+/// Held-Karp, i.e. the cheapest path over every subset of stops ending at every stop
+fn exact(costs: &[Vec<Option<u32>>], keep_first: bool, keep_last: bool) -> Vec<usize> {
+    let n = costs.len();
+    if n < 2 {
+        return (0..n).collect();
+    }
+    let full = (1usize << n) - 1;
+    let mut best = vec![vec![u64::MAX; n]; 1 << n];
+    let mut prev = vec![vec![usize::MAX; n]; 1 << n];
+    for start in 0..n {
+        if (keep_first && start != 0) || (keep_last && start == n - 1) {
+            continue;
+        }
+        best[1 << start][start] = 0;
+    }
+
+    for set in 1..=full {
+        for last in 0..n {
+            let cost = best[set][last];
+            if cost == u64::MAX {
+                continue;
+            }
+            for next in (0..n).filter(|next| set & (1 << next) == 0) {
+                let with_next = set | (1 << next);
+                // the last stop can only be visited at the end
+                if keep_last && next == n - 1 && with_next != full {
+                    continue;
+                }
+                let step = if let Some(step) = costs[last][next] { step } else { continue };
+                let cost = cost + u64::from(step);
+                if cost < best[with_next][next] {
+                    best[with_next][next] = cost;
+                    prev[with_next][next] = last;
+                }
+            }
+        }
+    }
+
+    let ends: Vec<usize> = if keep_last { vec![n - 1] } else { (0..n).collect() };
+    let mut last = *ends.iter().min_by_key(|end| best[full][**end]).unwrap();
+    if best[full][last] == u64::MAX {
+        // nothing reachable, the caller reports it
+        return (0..n).collect();
+    }
+    let mut order = Vec::with_capacity(n);
+    let mut set = full;
+    while last != usize::MAX {
+        order.push(last);
+        let before = prev[set][last];
+        set &= !(1 << last);
+        last = before;
+    }
+    order.reverse();
+    order
+}
+
+/// Cheapest of the tours which always continue with the cheapest unvisited stop,
+/// starting at every stop which may come first
+fn nearest_neighbor(costs: &[Vec<Option<u32>>], keep_first: bool, keep_last: bool) -> Vec<usize> {
+    let n = costs.len();
+    let starts = if keep_first { 0..1 } else { 0..n - usize::from(keep_last) };
+    starts
+        .map(|start| nearest_neighbor_from(costs, start, keep_last))
+        .min_by_key(|order| total(costs, order).unwrap_or(u64::MAX))
+        .unwrap()
+}
+
+fn nearest_neighbor_from(costs: &[Vec<Option<u32>>], start: usize, keep_last: bool) -> Vec<usize> {
+    let n = costs.len();
+    let mut visited = vec![false; n];
+    let mut order = vec![start];
+    visited[start] = true;
+    if keep_last {
+        visited[n - 1] = true;
+    }
+    while order.len() < n - usize::from(keep_last) {
+        let current = *order.last().unwrap();
+        let next = (0..n)
+            .filter(|i| !visited[*i])
+            .min_by_key(|i| costs[current][*i].unwrap_or(u32::MAX))
+            .unwrap();
+        visited[next] = true;
+        order.push(next);
+    }
+    if keep_last {
+        order.push(n - 1);
+    }
+    order
+}
+
+/// Applies improving 2-opt and Or-opt moves until there are none left,
+/// both work for asymmetric costs since they compare whole orders
+fn improve(costs: &[Vec<Option<u32>>], order: &mut Vec<usize>, keep_first: bool, keep_last: bool) {
+    let start = usize::from(keep_first);
+    let end = order.len() - usize::from(keep_last);
+    let mut cost = total(costs, order).unwrap_or(u64::MAX);
+
+    let mut is_improved = true;
+    while is_improved {
+        is_improved = false;
+
+        // 2-opt: reverse a sequence of stops
+        for i in start..end {
+            for j in i + 1..end {
+                order[i..=j].reverse();
+                match total(costs, order) {
+                    Some(c) if c < cost => {
+                        cost = c;
+                        is_improved = true;
+                    }
+                    _ => order[i..=j].reverse(),
+                }
+            }
+        }
+
+        // Or-opt: move a short sequence of stops somewhere else
+        for length in 1..=MAX_SEGMENT {
+            for i in start..end.saturating_sub(length - 1) {
+                for j in start..=end - length {
+                    if i == j {
+                        continue;
+                    }
+                    let mut moved = order.clone();
+                    let segment: Vec<usize> = moved.drain(i..i + length).collect();
+                    moved.splice(j..j, segment);
+                    match total(costs, &moved) {
+                        Some(c) if c < cost => {
+                            cost = c;
+                            *order = moved;
+                            is_improved = true;
+                        }
+                        _ => ()
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn total(costs: &[Vec<Option<u32>>], order: &[usize]) -> Option<u64> {
+    order.windows(2)
+        .map(|pair| costs[pair[0]][pair[1]].map(u64::from))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::fixtures::Random;
+    use crate::graph::router::tsp::{exact, improve, nearest_neighbor, order, total};
+
+    fn random_costs(n: usize, random: &mut Random) -> Vec<Vec<Option<u32>>> {
+        // points on a plane with slightly asymmetric costs
+        let points: Vec<(f64, f64)> = (0..n).map(|_| (random.next() * 1000.0, random.next() * 1000.0)).collect();
+        (0..n).map(|i| (0..n).map(|j| {
+            let (dx, dy) = (points[i].0 - points[j].0, points[i].1 - points[j].1);
+            Some(((dx * dx + dy * dy).sqrt() * (1.0 + random.next() * 0.1)) as u32)
+        }).collect()).collect()
+    }
+
+    fn permutations(items: &[usize]) -> Vec<Vec<usize>> {
+        if items.len() <= 1 {
+            return vec![items.to_vec()];
+        }
+        let mut result = Vec::new();
+        for i in 0..items.len() {
+            let mut rest = items.to_vec();
+            let item = rest.remove(i);
+            for mut permutation in permutations(&rest) {
+                permutation.insert(0, item);
+                result.push(permutation);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn exact_is_optimal() {
+        let mut random = Random(3);
+        for &(keep_first, keep_last) in &[(false, false), (true, false), (true, true)] {
+            let costs = random_costs(7, &mut random);
+            let best = permutations(&(0..7).collect::<Vec<_>>()).into_iter()
+                .filter(|p| !keep_first || p[0] == 0)
+                .filter(|p| !keep_last || p[6] == 6)
+                .map(|p| total(&costs, &p).unwrap())
+                .min()
+                .unwrap();
+
+            let order = exact(&costs, keep_first, keep_last);
+            assert_eq!(total(&costs, &order).unwrap(), best);
+            if keep_first {
+                assert_eq!(order[0], 0);
+            }
+            if keep_last {
+                assert_eq!(order[6], 6);
+            }
+        }
+    }
+
+    #[test]
+    fn heuristic() {
+        let mut random = Random(5);
+        let costs = random_costs(30, &mut random);
+        let mut order = nearest_neighbor(&costs, true, true);
+        let initial = total(&costs, &order).unwrap();
+        improve(&costs, &mut order, true, true);
+
+        assert!(total(&costs, &order).unwrap() < initial);
+        assert_eq!(order[0], 0);
+        assert_eq!(order[29], 29);
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..30).collect::<Vec<_>>());
+
+        // close to optimal for sizes which can still be checked
+        let costs = random_costs(9, &mut random);
+        let mut order = nearest_neighbor(&costs, false, false);
+        improve(&costs, &mut order, false, false);
+        let optimal = total(&costs, &exact(&costs, false, false)).unwrap();
+        assert!(total(&costs, &order).unwrap() as f64 <= optimal as f64 * 1.1);
+    }
+
+    #[test]
+    fn unreachable() {
+        let costs = vec![
+            vec![Some(0), Some(5), None],
+            vec![Some(5), Some(0), None],
+            vec![None, None, Some(0)],
+        ];
+        assert!(order(&costs, false, false).is_err());
+    }
+
+    #[test]
+    fn nearest_neighbor_start() {
+        // stops on a line with the first one in the middle
+        let positions: [i64; 11] = [5, 0, 1, 2, 3, 4, 6, 7, 8, 9, 10];
+        let costs: Vec<Vec<Option<u32>>> = positions.iter()
+            .map(|a| positions.iter().map(|b| Some((a - b).unsigned_abs() as u32)).collect())
+            .collect();
+
+        let kept = nearest_neighbor(&costs, true, false);
+        assert_eq!(kept[0], 0);
+        assert_eq!(total(&costs, &kept), Some(15));
+        assert_eq!(total(&costs, &nearest_neighbor(&costs, false, false)), Some(10));
+    }
+}
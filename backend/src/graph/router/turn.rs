@@ -0,0 +1,173 @@
+use crate::graph::edge::Edge;
+use crate::graph::Graph;
+use crate::graph::router::route::Radius;
+use crate::osm::Coordinates;
+
+/// Deflections up to this many degrees count as going straight on
+const STRAIGHT_DEGREES: f32 = 30.0;
+const U_TURN_DEGREES: f32 = 170.0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Turn {
+    Straight,
+    /// Deflection from going straight on in degrees
+    Left(f32),
+    Right(f32),
+    /// Turning back onto the road one came from
+    Around,
+}
+
+impl Turn {
+    pub fn new(c1: &Coordinates, c2: &Coordinates, c3: &Coordinates) -> Self {
+        let deflection = Radius::gamma(c1, c2, c3).degrees()
+            .map_or(0.0, |degrees| 180.0 - degrees);
+
+        if deflection <= STRAIGHT_DEGREES {
+            Turn::Straight
+        } else if deflection >= U_TURN_DEGREES {
+            Turn::Around
+        } else if is_left(c1, c2, c3) {
+            Turn::Left(deflection)
+        } else {
+            Turn::Right(deflection)
+        }
+    }
+}
+
+impl Graph {
+    /// Bends of a road between two intersections are not considered as turns,
+    /// one-way roads joining it make the node an intersection
+    pub fn turn(&self, from: &Edge, to: &Edge) -> Turn {
+        if from.source_index == to.target_index {
+            return Turn::Around;
+        }
+        if self.degree(from.target_index) <= 2 {
+            return Turn::Straight;
+        }
+        Turn::new(
+            self.coordinates(from.source_index),
+            self.coordinates(from.target_index),
+            self.coordinates(to.target_index),
+        )
+    }
+}
+
+/// Sign of the cross product, using an equirectangular projection
+fn is_left(c1: &Coordinates, c2: &Coordinates, c3: &Coordinates) -> bool {
+    let scale = c2.lat().to_radians().cos();
+    let (x1, y1) = ((c2.lon() - c1.lon()) * scale, c2.lat() - c1.lat());
+    let (x2, y2) = ((c3.lon() - c2.lon()) * scale, c3.lat() - c2.lat());
+    x1 * y2 - y1 * x2 > 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::edge::{self, Edge};
+    use crate::graph::fixtures;
+    use crate::graph::names::Names;
+    use crate::graph::node::{self, Node};
+    use crate::graph::restriction::Restriction;
+    use crate::graph::router::options::{Params, Routing, Transport, TurnCosts};
+    use crate::graph::router::Router;
+    use crate::graph::router::turn::Turn;
+    use crate::osm::Coordinates;
+    use crate::osm::pbf::create_graph;
+    use crate::osm::restriction::Kind;
+
+    #[test]
+    fn turn() {
+        let center = Coordinates::from(48.0, 9.0);
+        let south = Coordinates::from(47.99, 9.0);
+        let turn = |lat, lon| Turn::new(&south, &center, &Coordinates::from(lat, lon));
+
+        assert_eq!(turn(48.01, 9.0), Turn::Straight);
+        assert_eq!(turn(48.01, 9.001), Turn::Straight);
+        assert!(matches!(turn(48.0, 8.99), Turn::Left(d) if (d - 90.0).abs() < 1.0));
+        assert!(matches!(turn(48.0, 9.01), Turn::Right(d) if (d - 90.0).abs() < 1.0));
+        assert!(matches!(turn(47.995, 9.01), Turn::Right(d) if d > 90.0));
+        assert_eq!(turn(47.99, 9.0001), Turn::Around);
+    }
+
+    #[test]
+    fn penalized_route() {
+        // the goal is reached by turning left at the intersection
+        // or by going straight on over a slightly longer road
+        let coordinates = [(48.0, 9.0), (48.002, 9.0), (48.002, 8.998), (48.004, 9.0), (48.0035, 8.9985)];
+        let edges = [
+            (0, 1, "residential"),
+            (1, 2, "residential"),
+            (1, 3, "residential"),
+            (2, 4, "residential"),
+            (3, 4, "residential"),
+        ];
+        let graph = fixtures::graph(&coordinates, &edges);
+        let route = |turn_costs| {
+            let mut params = Params::new(Transport::Car, Routing::Distance, false);
+            params.turn_costs = turn_costs;
+            Router::new(&graph, params).shortest_path(graph.coordinates(0), graph.coordinates(4)).unwrap()
+                .nodes.iter().map(|n| n.id).collect::<Vec<i64>>()
+        };
+
+        assert_eq!(route(None), vec![0, 1, 2, 4]);
+        assert_eq!(route(Some(TurnCosts { left: 0, right: 100, u_turn: 0 })), vec![0, 1, 2, 4]);
+        assert_eq!(route(Some(TurnCosts { left: 100, right: 0, u_turn: 0 })), vec![0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn one_way_junction() {
+        // a one-way road from the east ends at a north-south road
+        let coordinates = [(47.99, 9.0), (48.0, 9.0), (48.01, 9.0), (48.0, 9.01)];
+        let nodes = coordinates.iter().enumerate()
+            .map(|(i, (lat, lon))| Node::new(i as i64, Coordinates::from(*lat, *lon), node::Meta::new(None)))
+            .collect();
+        let meta = |tags| edge::Meta::new(&fixtures::way(tags), None).unwrap();
+        let two_way = meta(&[("highway", "residential")]);
+        let one_way = meta(&[("highway", "residential"), ("oneway", "yes")]);
+        let edges = vec![
+            Edge::new(0, 1, two_way.clone()),
+            Edge::new(1, 0, two_way.clone()),
+            Edge::new(1, 2, two_way.clone()),
+            Edge::new(2, 1, two_way),
+            Edge::new(3, 1, one_way),
+        ];
+        let graph = create_graph(nodes, edges, Vec::new(), Names::new());
+        let edge = |source, target| graph.edge(graph.edges_between(source, target).next().unwrap());
+
+        assert_eq!(graph.degree(1), 3);
+        assert_eq!(graph.turn(edge(0, 1), edge(1, 2)), Turn::Straight);
+        assert!(matches!(graph.turn(edge(3, 1), edge(1, 2)), Turn::Right(_)));
+        assert!(matches!(graph.turn(edge(3, 1), edge(1, 0)), Turn::Left(_)));
+    }
+
+    #[test]
+    fn u_turn() {
+        // turning left at the intersection is forbidden, so either
+        // turn around at the next node or take a long detour
+        let coordinates = [(48.0, 9.0), (48.002, 9.0), (48.002, 8.998), (48.004, 9.0), (48.006, 8.994)];
+        let edges = [
+            (0, 1, "residential"),
+            (1, 2, "residential"),
+            (1, 3, "residential"),
+            (3, 4, "residential"),
+            (4, 2, "residential"),
+        ];
+        let restrictions = vec![Restriction {
+            from_index: 0,
+            via_index: 1,
+            to_index: 2,
+            kind: Kind::No,
+            transport: Transport::Car,
+        }];
+        let graph = fixtures::restricted_graph(&coordinates, &edges, restrictions);
+        let route = |turn_costs| {
+            let mut params = Params::new(Transport::Car, Routing::Distance, false);
+            params.turn_costs = turn_costs;
+            Router::new(&graph, params).shortest_path(graph.coordinates(0), graph.coordinates(2)).unwrap()
+                .nodes.iter().map(|n| n.id).collect::<Vec<i64>>()
+        };
+
+        assert_eq!(route(None), vec![0, 1, 3, 4, 2]);
+        assert_eq!(route(Some(TurnCosts { left: 0, right: 0, u_turn: 100 })), vec![0, 1, 3, 1, 2]);
+        assert_eq!(route(Some(TurnCosts { left: 0, right: 0, u_turn: 1000 })), vec![0, 1, 3, 4, 2]);
+    }
+}
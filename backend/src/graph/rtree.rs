@@ -0,0 +1,294 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::osm::Coordinates;
+
+/// Maximum number of children of a tree node
+const NODE_SIZE: usize = 16;
+
+/// Static R-tree packed along a Hilbert curve, i.e. items close to each other on the curve
+/// share the same tree nodes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RTree {
+    /// Item indices in the order of the leaves
+    items: Vec<usize>,
+    /// Bounding boxes level by level, the first level are the items
+    /// and every box covers `NODE_SIZE` consecutive boxes of the level below
+    levels: Vec<Vec<Rect>>,
+}
+
+impl RTree {
+    /// Bulk loads the tree from the bounding boxes of the items, the index of a box is its item
+    pub fn new(rects: Vec<Rect>) -> Self {
+        let mut items: Vec<usize> = (0..rects.len()).collect();
+        if let Some(bounds) = rects.iter().cloned().reduce(|a, b| a.union(&b)) {
+            let keys: Vec<u64> = rects.iter().map(|r| bounds.hilbert(&r.center())).collect();
+            items.sort_by_key(|i| keys[*i]);
+        }
+
+        let mut levels = vec![items.iter().map(|i| rects[*i].clone()).collect::<Vec<Rect>>()];
+        while levels.last().unwrap().len() > NODE_SIZE {
+            let parents = levels.last().unwrap()
+                .chunks(NODE_SIZE)
+                .map(|children| children.iter().cloned().reduce(|a, b| a.union(&b)).unwrap())
+                .collect();
+            levels.push(parents);
+        }
+        Self {
+            items,
+            levels,
+        }
+    }
+
+    /// Items by increasing distance to the point in meters,
+    /// `distance` returns the exact distance of an item which has to be at least the one of its box
+    pub fn nearest<'a, F>(&'a self, point: &'a Coordinates, distance: F) -> Nearest<'a, F>
+        where F: Fn(usize) -> f64 {
+        let mut queue = BinaryHeap::new();
+        let top = self.levels.len() - 1;
+        for (i, rect) in self.levels[top].iter().enumerate() {
+            queue.push(Entry::new(rect.distance(point), top, i, false));
+        }
+        Nearest {
+            tree: self,
+            point,
+            distance,
+            queue,
+        }
+    }
+
+    /// Items within the distance in meters of the point together with their distance,
+    /// ordered by increasing distance. `distance` returns the exact distance of an item
+    /// which has to be at least the one of its box.
+    pub fn within<F>(&self, point: &Coordinates, meters: f64, distance: F) -> Vec<(usize, f64)>
+        where F: Fn(usize) -> f64 {
+        let mut items: Vec<(usize, f64)> = self.search(|rect| rect.distance(point) <= meters).into_iter()
+            .map(|i| (i, distance(i)))
+            .filter(|(_, d)| *d <= meters)
+            .collect();
+        items.sort_by(|a, b| a.1.total_cmp(&b.1));
+        items
+    }
+
+    /// Items whose box overlaps the given one
+    pub fn intersecting(&self, rect: &Rect) -> Vec<usize> {
+        self.search(|r| r.intersects(rect))
+    }
+
+    /// Items whose box matches, only children of matching boxes are visited
+    fn search<F>(&self, matches: F) -> Vec<usize> where F: Fn(&Rect) -> bool {
+        let mut items = Vec::new();
+        let top = self.levels.len() - 1;
+        let mut stack: Vec<(usize, usize)> = (0..self.levels[top].len()).map(|i| (top, i)).collect();
+        while let Some((level, index)) = stack.pop() {
+            if !matches(&self.levels[level][index]) {
+                continue;
+            }
+            if level == 0 {
+                items.push(self.items[index]);
+                continue;
+            }
+            let end = ((index + 1) * NODE_SIZE).min(self.levels[level - 1].len());
+            stack.extend((index * NODE_SIZE..end).map(|i| (level - 1, i)));
+        }
+        items
+    }
+}
+
+pub struct Nearest<'a, F> {
+    tree: &'a RTree,
+    point: &'a Coordinates,
+    distance: F,
+    queue: BinaryHeap<Entry>,
+}
+
+impl<F> Iterator for Nearest<'_, F> where F: Fn(usize) -> f64 {
+    /// Item index and its distance
+    type Item = (usize, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(entry) = self.queue.pop() {
+            if entry.is_exact {
+                return Some((self.tree.items[entry.index], entry.distance));
+            }
+            if entry.level == 0 {
+                // the exact distance is only computed once the box is the closest one
+                let distance = (self.distance)(self.tree.items[entry.index]);
+                self.queue.push(Entry::new(distance, 0, entry.index, true));
+                continue;
+            }
+            let children = &self.tree.levels[entry.level - 1];
+            let end = ((entry.index + 1) * NODE_SIZE).min(children.len());
+            let start = entry.index * NODE_SIZE;
+            for (i, child) in children[start..end].iter().enumerate() {
+                self.queue.push(Entry::new(child.distance(self.point), entry.level - 1, start + i, false));
+            }
+        }
+        None
+    }
+}
+
+/// Bounding box in the units of `Coordinates`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rect {
+    min_lat: i32,
+    min_lon: i32,
+    max_lat: i32,
+    max_lon: i32,
+}
+
+impl Rect {
+    pub fn new(a: &Coordinates, b: &Coordinates) -> Self {
+        Self {
+            min_lat: a.lat.min(b.lat),
+            min_lon: a.lon.min(b.lon),
+            max_lat: a.lat.max(b.lat),
+            max_lon: a.lon.max(b.lon),
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min_lat: self.min_lat.min(other.min_lat),
+            min_lon: self.min_lon.min(other.min_lon),
+            max_lat: self.max_lat.max(other.max_lat),
+            max_lon: self.max_lon.max(other.max_lon),
+        }
+    }
+
+    fn intersects(&self, other: &Self) -> bool {
+        self.min_lat <= other.max_lat && other.min_lat <= self.max_lat &&
+            self.min_lon <= other.max_lon && other.min_lon <= self.max_lon
+    }
+
+    fn center(&self) -> (i64, i64) {
+        ((i64::from(self.min_lat) + i64::from(self.max_lat)) / 2,
+         (i64::from(self.min_lon) + i64::from(self.max_lon)) / 2)
+    }
+
+    /// Distance in meters to the closest point of the box
+    fn distance(&self, point: &Coordinates) -> f64 {
+        let closest = Coordinates::new(
+            point.lat.clamp(self.min_lat, self.max_lat),
+            point.lon.clamp(self.min_lon, self.max_lon),
+        );
+        closest.distance(point)
+    }
+
+    /// Position of the point on a Hilbert curve filling this box
+    fn hilbert(&self, (lat, lon): &(i64, i64)) -> u64 {
+        const ORDER: u32 = 16;
+        let side = (1i64 << ORDER) - 1;
+        let scale = |value: i64, min: i32, max: i32| {
+            let range = (i64::from(max) - i64::from(min)).max(1);
+            ((value - i64::from(min)) * side / range) as u64
+        };
+        let mut x = scale(*lon, self.min_lon, self.max_lon);
+        let mut y = scale(*lat, self.min_lat, self.max_lat);
+
+        let mut d = 0;
+        let mut s = 1u64 << (ORDER - 1);
+        while s > 0 {
+            let rx = u64::from(x & s > 0);
+            let ry = u64::from(y & s > 0);
+            d += s * s * ((3 * rx) ^ ry);
+            // rotate the quadrant
+            if ry == 0 {
+                if rx == 1 {
+                    x = s - 1 - (x & (s - 1));
+                    y = s - 1 - (y & (s - 1));
+                }
+                std::mem::swap(&mut x, &mut y);
+            }
+            s /= 2;
+        }
+        d
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    distance: f64,
+    level: usize,
+    index: usize,
+    /// Distance of the item itself instead of its box
+    is_exact: bool,
+}
+
+impl Entry {
+    fn new(distance: f64, level: usize, index: usize, is_exact: bool) -> Self {
+        Self {
+            distance,
+            level,
+            index,
+            is_exact,
+        }
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // min-heap, exact distances first on ties so items aren't expanded needlessly
+        other.distance.total_cmp(&self.distance)
+            .then_with(|| self.is_exact.cmp(&other.is_exact))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::fixtures::Random;
+    use crate::graph::rtree::{Rect, RTree};
+    use crate::osm::Coordinates;
+
+    #[test]
+    fn same_as_linear_scan() {
+        let mut random = Random(7);
+        let points: Vec<Coordinates> = (0..1000)
+            .map(|_| Coordinates::from(48.0 + random.next(), 9.0 + random.next()))
+            .collect();
+        let tree = RTree::new(points.iter().map(|p| Rect::new(p, p)).collect());
+
+        for _ in 0..20 {
+            let query = Coordinates::from(47.9 + random.next() * 1.2, 8.9 + random.next() * 1.2);
+            let mut expected: Vec<(usize, f64)> = points.iter()
+                .map(|p| p.distance(&query))
+                .enumerate()
+                .collect();
+            expected.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+            let actual: Vec<(usize, f64)> = tree.nearest(&query, |i| points[i].distance(&query))
+                .take(10)
+                .collect();
+            assert_eq!(actual, expected[..10].to_vec());
+
+            let within = tree.within(&query, 5000.0, |i| points[i].distance(&query));
+            let close: Vec<(usize, f64)> = expected.iter().copied().filter(|(_, d)| *d <= 5000.0).collect();
+            assert_eq!(within, close);
+
+            let corner = Coordinates::from(query.lat() + 0.1, query.lon() + 0.1);
+            let mut inside = tree.intersecting(&Rect::new(&query, &corner));
+            inside.sort_unstable();
+            let expected: Vec<usize> = (0..points.len())
+                .filter(|i| points[*i].lat >= query.lat && points[*i].lat <= corner.lat &&
+                    points[*i].lon >= query.lon && points[*i].lon <= corner.lon)
+                .collect();
+            assert_eq!(inside, expected);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::graph::edge::Meta;
+use crate::osm::Highway::{Ferry, LivingStreet, Motorway, MotorwayLink, Primary, PrimaryLink, Residential,
+                          Secondary, SecondaryLink, Service, Tertiary, TertiaryLink, Trunk, TrunkLink,
+                          Unclassified};
+use crate::osm::Surface::{self, Asphalt, Cobblestone, Compacted, Concrete, Dirt, Earth, FineGravel, Grass,
+                          GrassPaver, Gravel, Ground, Metal, Mud, Paved, PavingStones, Pebblestone,
+                          Plastic, Sand, Sett, Unpaved, Wood};
+
+/// Lateral acceleration in m/s² which is still comfortable in curves
+const LATERAL_ACCELERATION: f32 = 3.0;
+/// Grade in percent from which on cars slow down
+const STEEP_GRADE: f32 = 4.0;
+/// Share of speed lost per percent of grade beyond a steep grade
+const GRADE_SLOWDOWN: f32 = 0.02;
+/// Share of the speed which is kept even on the steepest roads
+const MIN_GRADE_FACTOR: f32 = 0.6;
+/// Seconds lost at every intersection
+pub const INTERSECTION_DELAY: u32 = 3;
+/// Highest speed of bikes in km/h, which is only reached downhill
+pub const MAX_BIKE_SPEED: u8 = 50;
+/// Bikes are pushed if riding would be slower
+const PUSHING_SPEED: f32 = 4.0;
+/// Highest speed of Tobler's hiking function in km/h, reached at a slight descent
+pub const MAX_WALK_SPEED: u8 = 6;
+const GRAVITY: f32 = 9.81;
+const AIR_DENSITY: f32 = 1.2;
+/// Drag coefficient times frontal area in m² of an upright rider
+const DRAG_AREA: f32 = 0.5;
+
+/// Speed in km/h a car actually drives on the edge, i.e. the max speed reduced by
+/// how tight the curves are, how steep and rough the road is and how busy its class
+pub fn car(meta: &Meta) -> f32 {
+    let speed = f32::from(meta.max_speed.speed) * class_factor(meta) * grade_factor(meta.grade) *
+        meta.surface.map_or(1.0, surface_factor);
+    match meta.radius {
+        Some(radius) => speed.min(curve_speed(f32::from(radius))),
+        None => speed
+    }
+}
+
+/// Speed in km/h of a rider with the given power in watts and weight of rider and bike in kg,
+/// i.e. the speed at which the power equals the resistance of rolling, climbing and the air
+pub fn bike(meta: &Meta, power: f32, weight: f32) -> f32 {
+    let slope = (f32::from(meta.grade.unwrap_or(0)) / 1000.0).atan();
+    let rolling = meta.surface.map_or(0.005, rolling_resistance);
+    // constant part of the resistance in N, negative on steep descents
+    let force = weight * GRAVITY * (rolling * slope.cos() + slope.sin());
+    let power_needed = |ms: f32| 0.5 * AIR_DENSITY * DRAG_AREA * ms.powi(3) + force * ms;
+
+    // the power needed is negative up to a single root and grows from there on
+    let (mut low, mut high) = (0.0, f32::from(MAX_BIKE_SPEED) / 3.6);
+    if power_needed(high) < power {
+        return f32::from(MAX_BIKE_SPEED);
+    }
+    for _ in 0..50 {
+        let middle = (low + high) / 2.0;
+        if power_needed(middle) < power {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    (low * 3.6).max(PUSHING_SPEED)
+}
+
+/// Tobler's hiking function, fastest at a descent of 5%
+pub fn walk(meta: &Meta) -> f32 {
+    let slope = f32::from(meta.grade.unwrap_or(0)) / 1000.0;
+    f32::from(MAX_WALK_SPEED) * (-3.5 * (slope + 0.05).abs()).exp()
+}
+
+/// Highest speed in km/h at which the curve can be taken comfortably
+fn curve_speed(radius: f32) -> f32 {
+    (LATERAL_ACCELERATION * radius).sqrt() * 3.6
+}
+
+/// Minor roads are rarely driven at their max speed due to traffic, parked cars and visibility
+fn class_factor(meta: &Meta) -> f32 {
+    match meta.highway {
+        Motorway | Trunk | MotorwayLink | TrunkLink | Ferry => 0.9,
+        Primary | Secondary | PrimaryLink | SecondaryLink => 0.85,
+        Tertiary | Unclassified | TertiaryLink => 0.8,
+        Residential | LivingStreet | Service => 0.7,
+        _ => 0.6
+    }
+}
+
+/// Steep climbs and descents are driven slower, grades are given in tenths of a percent
+fn grade_factor(grade: Option<i16>) -> f32 {
+    let grade = grade.map_or(0.0, |g| f32::from(g.abs()) / 10.0);
+    (1.0 - GRADE_SLOWDOWN * (grade - STEEP_GRADE).max(0.0)).max(MIN_GRADE_FACTOR)
+}
+
+fn surface_factor(surface: Surface) -> f32 {
+    match surface {
+        Paved | Asphalt | Concrete => 1.0,
+        PavingStones | Metal | Wood | Plastic => 0.8,
+        Compacted | FineGravel => 0.7,
+        Sett | Cobblestone => 0.6,
+        Gravel | Pebblestone | GrassPaver => 0.5,
+        Unpaved | Dirt | Earth | Ground | Grass => 0.4,
+        Mud | Sand => 0.3,
+    }
+}
+
+fn rolling_resistance(surface: Surface) -> f32 {
+    match surface {
+        Asphalt => 0.004,
+        Paved | Concrete => 0.005,
+        PavingStones | Metal | Wood | Plastic => 0.008,
+        Compacted | FineGravel => 0.01,
+        Sett | Cobblestone => 0.015,
+        Gravel | Pebblestone | GrassPaver => 0.02,
+        Unpaved | Dirt | Earth | Ground => 0.025,
+        Grass => 0.04,
+        Mud | Sand => 0.08,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::edge::Meta;
+    use crate::graph::fixtures;
+    use crate::graph::router::options::{Params, Routing, SpeedModel, Transport};
+    use crate::graph::router::Router;
+    use crate::graph::speed::{bike, car, curve_speed, walk};
+    use crate::osm::Surface;
+
+    fn meta(tags: &[(&str, &str)]) -> Meta {
+        Meta::new(&fixtures::way(tags), None).unwrap()
+    }
+
+    #[test]
+    fn car_speed() {
+        let mut primary = meta(&[("highway", "primary"), ("maxspeed", "100")]);
+        assert!((car(&primary) - 85.0).abs() < 0.1);
+
+        // hairpin
+        primary.radius = Some(20);
+        assert!((car(&primary) - curve_speed(20.0)).abs() < 0.1);
+        assert!(car(&primary) < 30.0);
+
+        primary.radius = None;
+        primary.grade = Some(-100);
+        assert!((car(&primary) - 85.0 * 0.88).abs() < 0.1);
+        primary.grade = Some(300);
+        assert!((car(&primary) - 85.0 * 0.6).abs() < 0.1);
+
+        primary.grade = None;
+        primary.surface = Some(Surface::Gravel);
+        assert!((car(&primary) - 42.5).abs() < 0.1);
+
+        let residential = meta(&[("highway", "residential"), ("maxspeed", "30")]);
+        assert!(car(&residential) < 30.0);
+    }
+
+    #[test]
+    fn bike_speed() {
+        let mut road = meta(&[("highway", "tertiary"), ("surface", "asphalt")]);
+        let flat = bike(&road, 120.0, 90.0);
+        assert!(flat > 20.0 && flat < 30.0);
+        assert!(bike(&road, 200.0, 90.0) > flat);
+
+        road.grade = Some(60);
+        let climb = bike(&road, 120.0, 90.0);
+        assert!(climb < flat / 2.0);
+        assert!(bike(&road, 120.0, 70.0) > climb);
+
+        road.grade = Some(-80);
+        assert!(bike(&road, 120.0, 90.0) > flat);
+        road.grade = Some(-300);
+        assert_eq!(bike(&road, 120.0, 90.0), 50.0);
+        road.grade = Some(300);
+        assert_eq!(bike(&road, 120.0, 90.0), 4.0);
+
+        road.grade = None;
+        road.surface = Some(Surface::Gravel);
+        assert!(bike(&road, 120.0, 90.0) < flat);
+    }
+
+    #[test]
+    fn walk_speed() {
+        let mut path = meta(&[("highway", "path")]);
+        assert!((walk(&path) - 5.04).abs() < 0.01);
+        path.grade = Some(-50);
+        assert!((walk(&path) - 6.0).abs() < 0.01);
+        path.grade = Some(200);
+        assert!((walk(&path) - 2.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn terrain_routing() {
+        // direct road over a hill from west to east and a flat detour over the north
+        let coordinates = [
+            (48.0, 8.99, 100.0),
+            (48.0, 9.0, 150.0),
+            (48.0, 9.01, 110.0),
+            (48.004, 9.0, 105.0),
+        ];
+        let edges = [(0, 1, "tertiary"), (1, 2, "tertiary"), (0, 3, "tertiary"), (3, 2, "tertiary")];
+        let graph = fixtures::hilly_graph(&coordinates, &edges);
+        let route = |speed_model| {
+            let mut params = Params::new(Transport::Bike, Routing::Time, false);
+            params.speed_model = speed_model;
+            let route = Router::new(&graph, params)
+                .shortest_path(graph.coordinates(0), graph.coordinates(2)).unwrap();
+            (route.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), route.time)
+        };
+
+        let (constant, constant_time) = route(SpeedModel::Constant);
+        assert_eq!(constant, vec![0, 1, 2]);
+        let (terrain, terrain_time) = route(SpeedModel::Terrain { power: 120.0, weight: 90.0 });
+        assert_eq!(terrain, vec![0, 3, 2]);
+        assert_ne!(constant_time, terrain_time);
+    }
+}
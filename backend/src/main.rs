@@ -1,11 +1,16 @@
 use std::{env, process};
 use std::path::Path;
+use std::str::FromStr;
 use std::time::Instant;
 
 use log::debug;
+use strum::IntoEnumIterator;
 
 use crate::graph::Graph;
+use crate::osm::maxspeed::Country;
 
+mod geojson;
+mod gpx;
 mod graph;
 mod logger;
 mod osm;
@@ -34,8 +39,29 @@ fn graph() -> Graph {
         Graph::from_bin(&bin_name)
     } else {
         debug!("No existing graph found, parsing...");
-        let graph = Graph::from_pbf(&pbf_name);
+        // e.g. --country=DE for the default speed limits of Germany
+        let country = env::args()
+            .find_map(|arg| arg.strip_prefix("--country=").map(country));
+        let mut graph = Graph::from_pbf(&pbf_name, country);
+        if env::args().any(|arg| arg == "--contract") {
+            let now = Instant::now();
+            graph.contract();
+            debug!("Contracting the graph took {:?} seconds", now.elapsed().as_secs());
+        }
+        if env::args().any(|arg| arg == "--landmarks") {
+            let now = Instant::now();
+            graph.select_landmarks();
+            debug!("Selecting landmarks took {:?} seconds", now.elapsed().as_secs());
+        }
         graph.save(&bin_name);
         graph
     }
 }
+
+fn country(code: &str) -> Country {
+    Country::from_str(code).unwrap_or_else(|_| {
+        let codes = Country::iter().map(|c| format!("{:?}", c)).collect::<Vec<String>>();
+        println!("Unknown country {}, please provide one of {}", code, codes.join(", "));
+        process::exit(1);
+    })
+}
@@ -0,0 +1,248 @@
+use std::str::FromStr;
+
+use osmpbfreader::Way;
+use strum_macros::{EnumIter, EnumString};
+
+use crate::osm::{Highway, Kmh};
+
+/// Advisory speed on roads without a speed limit
+const NO_LIMIT: u8 = 130;
+/// Walking pace, e.g. for living streets
+const WALKING_PACE: u8 = 7;
+
+/// Countries with known statutory speed limits
+#[derive(Debug, Eq, PartialEq, Clone, Copy, EnumIter, EnumString)]
+pub enum Country {
+    AT,
+    BE,
+    CH,
+    CZ,
+    DE,
+    DK,
+    ES,
+    FR,
+    GB,
+    IT,
+    NL,
+    PL,
+    RO,
+}
+
+/// Statutory speed limits of a country in km/h
+struct Limits {
+    urban: u8,
+    rural: u8,
+    trunk: u8,
+    motorway: u8,
+    living_street: u8,
+}
+
+impl Country {
+    fn limits(self) -> Limits {
+        let (urban, rural, trunk, motorway, living_street) = match self {
+            Self::AT => (50, 100, 100, 130, 5),
+            Self::BE => (50, 90, 120, 120, 20),
+            Self::CH => (50, 80, 100, 120, 20),
+            Self::CZ => (50, 90, 110, 130, 20),
+            Self::DE => (50, 100, 100, NO_LIMIT, WALKING_PACE),
+            Self::DK => (50, 80, 80, 130, 15),
+            Self::ES => (50, 90, 100, 120, 20),
+            Self::FR => (50, 80, 110, 130, 20),
+            // 30, 60 and 70 mph
+            Self::GB => (48, 96, 112, 112, 16),
+            Self::IT => (50, 90, 110, 130, 20),
+            Self::NL => (50, 80, 100, 100, 15),
+            Self::PL => (50, 90, 120, 140, 20),
+            Self::RO => (50, 90, 100, 130, 20),
+        };
+        Limits {
+            urban,
+            rural,
+            trunk,
+            motorway,
+            living_street,
+        }
+    }
+
+    /// Default speed of a highway without a tagged limit, roads outside of residential
+    /// areas are assumed to be rural, except for tertiary and unclassified ones
+    /// which often lead through towns
+    fn default_speed(self, highway: Highway) -> Option<u8> {
+        let limits = self.limits();
+        let speed = match highway {
+            Highway::Motorway => limits.motorway,
+            Highway::Trunk => limits.trunk,
+            Highway::Primary | Highway::Secondary => limits.rural,
+            Highway::Tertiary | Highway::Unclassified => (limits.urban + limits.rural) / 2,
+            Highway::Residential => limits.urban.min(30),
+            Highway::LivingStreet => limits.living_street,
+            _ => return None
+        };
+        Some(speed)
+    }
+}
+
+/// Speed limit for travelling the way forward or backward. Direction-specific tags take
+/// precedence, ways without a usable limit get the default of the country, which is taken from
+/// the zone tags of the way if possible.
+pub fn max_speed(way: &Way, highway: Highway, country: Option<Country>, is_forward: bool) -> Kmh {
+    let direction = if is_forward { "maxspeed:forward" } else { "maxspeed:backward" };
+    let tagged = way.tags.get(direction)
+        .or_else(|| way.tags.get("maxspeed"))
+        .and_then(|value| parse(value))
+        .or_else(|| ["maxspeed:type", "source:maxspeed", "zone:maxspeed"].iter()
+            .filter_map(|key| way.tags.get(*key))
+            .find_map(|value| parse(value)));
+
+    let country = zone_country(way).or(country);
+    let speed = tagged
+        .or_else(|| country.and_then(|c| c.default_speed(highway)))
+        .or_else(|| highway.default_speed().map(|s| s.speed))
+        .unwrap();
+
+    // time-dependent routing isn't supported, so the lower limit of a time window
+    // is assumed to avoid underestimating the travel time
+    let conditional = way.tags.get("maxspeed:conditional").map_or_else(Vec::new, |value| conditions(value));
+    let speed = conditional.into_iter()
+        .filter(|(_, condition)| is_time(condition))
+        .filter_map(|(value, _)| parse(value))
+        .fold(speed, u8::min);
+    Kmh::new(speed)
+}
+
+/// Lowest of several speeds separated by semicolons
+fn parse(value: &str) -> Option<u8> {
+    value.split(';')
+        .filter_map(|value| parse_single(value.trim()))
+        .min()
+}
+
+fn parse_single(value: &str) -> Option<u8> {
+    if let Ok(speed) = value.parse::<u8>() {
+        return Some(speed);
+    }
+    match value {
+        "none" => return Some(NO_LIMIT),
+        "walk" => return Some(WALKING_PACE),
+        // variable limits depend on the traffic
+        "signals" | "variable" => return None,
+        _ => ()
+    }
+    if let Some((number, unit)) = value.split_once(' ') {
+        let number = number.parse::<f32>().ok()?;
+        let factor = match unit {
+            "mph" => 1.609_344,
+            "knots" => 1.852,
+            "km/h" => 1.0,
+            _ => return None
+        };
+        return Some((number * factor).round() as u8);
+    }
+    zone(value)
+}
+
+/// Zone like DE:urban, DE:zone30 or GB:nsl_single
+fn zone(value: &str) -> Option<u8> {
+    let (country, zone) = value.split_once(':')?;
+    let limits = Country::from_str(country).ok()?.limits();
+    let speed = match zone {
+        "urban" | "nsl_restricted" => limits.urban,
+        "rural" | "nsl_single" => limits.rural,
+        "trunk" | "nsl_dual" | "motorroad" => limits.trunk,
+        "motorway" => limits.motorway,
+        "living_street" => limits.living_street,
+        "bicycle_road" => 30,
+        _ => zone.trim_start_matches("zone").trim_start_matches(':').parse().ok()?
+    };
+    Some(speed)
+}
+
+/// Country of the first zone tag of the way
+fn zone_country(way: &Way) -> Option<Country> {
+    ["maxspeed", "maxspeed:type", "source:maxspeed", "zone:maxspeed"].iter()
+        .filter_map(|key| way.tags.get(*key))
+        .filter_map(|value| value.split_once(':'))
+        .find_map(|(country, _)| Country::from_str(country).ok())
+}
+
+/// Pairs of value and condition, e.g. `30 @ (Mo-Fr 07:00-17:00); 80 @ wet`
+fn conditions(value: &str) -> Vec<(&str, &str)> {
+    let mut conditions = Vec::new();
+    let mut rest = value;
+    while let Some(at) = rest.find('@') {
+        let value = rest[..at].trim().trim_start_matches(';').trim();
+        let after = rest[at + 1..].trim_start();
+        // conditions in parentheses may contain semicolons themselves
+        let end = if after.starts_with('(') {
+            after.find(')').map_or(after.len(), |end| end + 1)
+        } else {
+            after.find(';').unwrap_or(after.len())
+        };
+        conditions.push((value, after[..end].trim()));
+        rest = &after[end..];
+    }
+    conditions
+}
+
+/// Whether the condition is a time window, as opposed to e.g. weather or vehicle properties
+fn is_time(condition: &str) -> bool {
+    condition.contains(':') || condition.contains("sunset") || condition.contains("sunrise") ||
+        ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su", "PH"].iter().any(|day| condition.contains(day))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::fixtures;
+    use crate::osm::Highway;
+    use crate::osm::maxspeed::{conditions, Country, max_speed, parse};
+
+    fn speed(tags: &[(&str, &str)], country: Option<Country>) -> u8 {
+        max_speed(&fixtures::way(tags), Highway::Tertiary, country, true).speed
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(parse("50"), Some(50));
+        assert_eq!(parse("30 mph"), Some(48));
+        assert_eq!(parse("10 knots"), Some(19));
+        assert_eq!(parse("none"), Some(130));
+        assert_eq!(parse("walk"), Some(7));
+        assert_eq!(parse("signals"), None);
+        assert_eq!(parse("DE:urban"), Some(50));
+        assert_eq!(parse("DE:rural"), Some(100));
+        assert_eq!(parse("RO:motorway"), Some(130));
+        assert_eq!(parse("DE:zone30"), Some(30));
+        assert_eq!(parse("DE:zone:20"), Some(20));
+        assert_eq!(parse("GB:nsl_single"), Some(96));
+        assert_eq!(parse("XX:urban"), None);
+        assert_eq!(parse("60;50"), Some(50));
+    }
+
+    #[test]
+    fn defaults() {
+        // generic defaults without a country
+        assert_eq!(speed(&[], None), 100);
+        assert_eq!(speed(&[], Some(Country::DE)), 75);
+        assert_eq!(speed(&[], Some(Country::FR)), 65);
+        // the zone tag tells the country
+        assert_eq!(speed(&[("maxspeed:type", "DE:rural")], None), 100);
+        assert_eq!(speed(&[("maxspeed", "signals"), ("source:maxspeed", "FR:urban")], None), 50);
+        assert_eq!(max_speed(&fixtures::way(&[]), Highway::Motorway, Some(Country::DE), true).speed, 130);
+    }
+
+    #[test]
+    fn directions() {
+        let tags = [("maxspeed", "100"), ("maxspeed:backward", "70")];
+        let way = fixtures::way(&tags);
+        assert_eq!(max_speed(&way, Highway::Primary, None, true).speed, 100);
+        assert_eq!(max_speed(&way, Highway::Primary, None, false).speed, 70);
+    }
+
+    #[test]
+    fn conditional() {
+        assert_eq!(conditions("30 @ (Mo-Fr 07:00-17:00; Sa 08:00-12:00); 80 @ wet"),
+                   vec![("30", "(Mo-Fr 07:00-17:00; Sa 08:00-12:00)"), ("80", "wet")]);
+        assert_eq!(speed(&[("maxspeed", "50"), ("maxspeed:conditional", "30 @ (22:00-06:00)")], None), 30);
+        assert_eq!(speed(&[("maxspeed", "100"), ("maxspeed:conditional", "80 @ wet")], None), 100);
+    }
+}
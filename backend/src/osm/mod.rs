@@ -1,5 +1,4 @@
 use std::fmt;
-use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 
 use osmpbfreader::Way;
@@ -8,18 +7,82 @@ use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
 use serde::ser::{Serializer, SerializeStruct};
 use strum_macros::EnumString;
 
+pub mod maxspeed;
 pub mod pbf;
+pub mod restriction;
+
+/// Directions a way may be travelled in relative to the order of its nodes
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Direction {
+    Both,
+    Forward,
+    Backward,
+    /// Reversible ways change their direction over the day
+    Neither,
+}
+
+impl Direction {
+    fn from(value: &str) -> Option<Self> {
+        match value {
+            "yes" | "true" | "1" => Some(Self::Forward),
+            "-1" | "reverse" => Some(Self::Backward),
+            "no" | "false" | "0" => Some(Self::Both),
+            "reversible" | "alternating" => Some(Self::Neither),
+            _ => None
+        }
+    }
 
-pub fn is_oneway(way: &Way) -> bool {
-    let tag = way.tags.get("oneway");
-    // not oneway assumed if not specified
-    if tag.is_none() {
-        return false;
+    pub fn allows(self, is_forward: bool) -> bool {
+        match self {
+            Self::Both => true,
+            Self::Forward => is_forward,
+            Self::Backward => !is_forward,
+            Self::Neither => false,
+        }
     }
-    tag.unwrap() == "yes"
 }
 
-#[derive(Debug, Clone)]
+/// Allowed directions of a way per transport mode
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Oneway {
+    pub vehicle: Direction,
+    pub bike: Direction,
+    pub foot: Direction,
+}
+
+impl Oneway {
+    pub fn from(way: &Way, highway: Highway) -> Self {
+        let tag = |key: &str| way.tags.get(key).and_then(|value| Direction::from(value));
+
+        // roundabouts and motorways are oneway unless tagged otherwise
+        let is_implied = way.tags.contains("junction", "roundabout") ||
+            way.tags.contains("junction", "circular") ||
+            matches!(highway, Highway::Motorway | Highway::MotorwayLink);
+        let vehicle = tag("oneway")
+            .unwrap_or(if is_implied { Direction::Forward } else { Direction::Both });
+
+        // lanes for cycling against the traffic
+        let is_opposite = ["cycleway", "cycleway:left", "cycleway:right", "cycleway:both"].iter()
+            .filter_map(|key| way.tags.get(*key))
+            .any(|value| value.starts_with("opposite"));
+        let bike = tag("oneway:bicycle")
+            .unwrap_or(if is_opposite { Direction::Both } else { vehicle });
+
+        // pedestrians only keep to the direction on their own ways
+        let is_footway = matches!(highway, Highway::Footway | Highway::Path | Highway::Pedestrian | Highway::Steps);
+        let foot = tag("oneway:foot")
+            .or_else(|| if is_footway { tag("oneway") } else { None })
+            .unwrap_or(Direction::Both);
+
+        Self {
+            vehicle,
+            bike,
+            foot,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Coordinates {
     pub lat: i32,
     pub lon: i32,
@@ -48,40 +111,29 @@ impl Coordinates {
         f64::from(self.lon) * 1e-7
     }
 
-    fn lat_rounded(&self) -> i32 {
-        (self.lat() * 10.0).round() as i32
-    }
-
-    fn lon_rounded(&self) -> i32 {
-        (self.lon() * 10.0).round() as i32
-    }
-
     /// Haversine distance
     pub fn distance(&self, other: &Self) -> f64 {
-        let theta1 = self.lon().to_radians();
-        let theta2 = other.lon().to_radians();
-        let delta_theta = (other.lon() - self.lon()).to_radians();
-        let delta_lambda = (other.lat() - self.lat()).to_radians();
-        let a = (delta_theta / 2.0).sin().powi(2)
-            + theta1.cos() * theta2.cos() * (delta_lambda / 2.0).sin().powi(2);
+        let phi1 = self.lat().to_radians();
+        let phi2 = other.lat().to_radians();
+        let delta_phi = (other.lat() - self.lat()).to_radians();
+        let delta_lambda = (other.lon() - self.lon()).to_radians();
+        let a = (delta_phi / 2.0).sin().powi(2)
+            + phi1.cos() * phi2.cos() * (delta_lambda / 2.0).sin().powi(2);
         let c = 2.0 * a.sqrt().asin();
         6_371_000.0 * c
     }
-}
-
-impl Eq for Coordinates {}
-
-impl PartialEq for Coordinates {
-    fn eq(&self, other: &Self) -> bool {
-        self.lat_rounded() == other.lat_rounded() &&
-            self.lon_rounded() == other.lon_rounded()
-    }
-}
 
-impl Hash for Coordinates {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.lat_rounded().hash(state);
-        self.lon_rounded().hash(state);
+    /// Point reached when going the distance in meters along the bearing in degrees
+    pub fn destination(&self, bearing: f64, distance: f64) -> Self {
+        let lat = self.lat().to_radians();
+        let lon = self.lon().to_radians();
+        let bearing = bearing.to_radians();
+        let delta = distance / 6_371_000.0;
+
+        let dest_lat = (lat.sin() * delta.cos() + lat.cos() * delta.sin() * bearing.cos()).asin();
+        let dest_lon = lon + (bearing.sin() * delta.sin() * lat.cos())
+            .atan2(delta.cos() - lat.sin() * dest_lat.sin());
+        Self::from(dest_lat.to_degrees(), dest_lon.to_degrees())
     }
 }
 
@@ -100,7 +152,6 @@ impl<'de> serde::de::Deserialize<'de> for Coordinates {
     fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error> where
         D: Deserializer<'de> {
         enum Field { Lat, Lon }
-        ;
 
         impl<'de> serde::de::Deserialize<'de> for Field {
             fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
@@ -199,10 +250,16 @@ pub enum Highway {
     Path,
 
     Cycleway,
+
+    /// Ferry route, not a highway in OSM but routable as one
+    Ferry,
 }
 
 impl Highway {
     pub fn from(way: &Way) -> Option<Self> {
+        if way.tags.contains("route", "ferry") {
+            return Some(Self::Ferry);
+        }
         let tag = way.tags.get("highway")?;
         Self::from_str(tag).ok()
     }
@@ -223,6 +280,7 @@ impl Highway {
             Self::TertiaryLink => 50,
             Self::LivingStreet => 5,
             Self::Service => 30,
+            Self::Ferry => 20,
             _ => 30
         };
         Some(Kmh::new(speed))
@@ -239,23 +297,6 @@ impl Kmh {
         Self { speed }
     }
 
-    pub fn from(way: &Way) -> Option<Self> {
-        let tag = way.tags.get("maxspeed")?;
-
-        if let Ok(speed) = tag.parse::<u8>() {
-            Some(Self::new(speed))
-        } else {
-            let speed: Vec<&str> = tag.split(' ').collect();
-            if *speed.get(1)? == "mph" {
-                let mph = speed.get(0)?
-                    .parse::<u8>().ok()?;
-                let kmh = mph as f32 * 1.609_344;
-                return Some(Self::new(kmh as u8));
-            }
-            None
-        }
-    }
-
     pub fn time(self, distance: u32) -> u32 {
         let ms = self.speed as f32 / 3.6;
         (distance as f32 / ms).round() as u32
@@ -297,7 +338,9 @@ impl Surface {
 
 #[cfg(test)]
 mod tests {
-    use crate::osm::Kmh;
+    use crate::graph::fixtures;
+    use crate::osm::{Coordinates, Direction, Highway, Kmh, Oneway};
+    use crate::osm::Direction::{Backward, Both, Forward, Neither};
 
     #[test]
     fn time() {
@@ -305,4 +348,53 @@ mod tests {
         assert_eq!(36, Kmh::new(20).time(200));
         assert_eq!(144, Kmh::new(5).time(200));
     }
+
+    #[test]
+    fn destination() {
+        let start = Coordinates::from(48.0, 9.0);
+        for bearing in &[0.0, 45.0, 180.0, 300.0] {
+            let destination = start.destination(*bearing, 5_000.0);
+            assert!((start.distance(&destination) - 5_000.0).abs() < 1.0);
+        }
+        assert!(start.destination(0.0, 1_000.0).lat() > start.lat());
+        assert!(start.destination(90.0, 1_000.0).lon() > start.lon());
+    }
+
+    #[test]
+    fn oneway() {
+        let oneway = |highway, tags: &[(&str, &str)]| {
+            let oneway = Oneway::from(&fixtures::way(tags), highway);
+            (oneway.vehicle, oneway.bike, oneway.foot)
+        };
+        let residential = |tags: &[(&str, &str)]| oneway(Highway::Residential, tags);
+
+        assert_eq!(residential(&[]), (Both, Both, Both));
+        for value in &["yes", "true", "1"] {
+            assert_eq!(residential(&[("oneway", value)]), (Forward, Forward, Both));
+        }
+        assert_eq!(residential(&[("oneway", "-1")]), (Backward, Backward, Both));
+        assert_eq!(residential(&[("oneway", "reversible")]), (Neither, Neither, Both));
+        assert_eq!(residential(&[("oneway", "unknown")]), (Both, Both, Both));
+
+        // implied
+        assert_eq!(residential(&[("junction", "roundabout")]), (Forward, Forward, Both));
+        assert_eq!(oneway(Highway::Motorway, &[]).0, Forward);
+        assert_eq!(oneway(Highway::MotorwayLink, &[("oneway", "no")]).0, Both);
+
+        // exceptions for bikes and pedestrians
+        assert_eq!(residential(&[("oneway", "yes"), ("oneway:bicycle", "no")]), (Forward, Both, Both));
+        assert_eq!(residential(&[("oneway", "yes"), ("cycleway", "opposite_lane")]), (Forward, Both, Both));
+        assert_eq!(residential(&[("oneway", "-1"), ("cycleway:left", "opposite")]), (Backward, Both, Both));
+        assert_eq!(residential(&[("oneway:bicycle", "yes")]), (Both, Forward, Both));
+        assert_eq!(oneway(Highway::Footway, &[("oneway", "yes")]), (Forward, Forward, Forward));
+        assert_eq!(residential(&[("oneway:foot", "-1")]).2, Backward);
+    }
+
+    #[test]
+    fn direction() {
+        assert!(Direction::Both.allows(true) && Direction::Both.allows(false));
+        assert!(Direction::Forward.allows(true) && !Direction::Forward.allows(false));
+        assert!(!Direction::Backward.allows(true) && Direction::Backward.allows(false));
+        assert!(!Direction::Neither.allows(true) && !Direction::Neither.allows(false));
+    }
 }
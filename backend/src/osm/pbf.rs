@@ -1,41 +1,79 @@
 use std::collections::HashMap;
 use std::fs::File;
-use std::mem;
 use std::path::Path;
 
 use log::debug;
-use osmpbfreader::{NodeId, OsmObj, OsmPbfReader};
+use osmpbfreader::{NodeId, OsmObj, OsmPbfReader, Way, WayId};
 
 use crate::graph::{edge, Graph, node};
 use crate::graph::edge::Edge;
+use crate::graph::names::Names;
 use crate::graph::node::Node;
-use crate::osm::{Coordinates, is_oneway};
+use crate::graph::restriction::Restriction;
+use crate::graph::router::route::Radius;
+use crate::osm::{Coordinates, Oneway};
+use crate::osm::maxspeed::{self, Country};
+use crate::osm::restriction::TurnRestriction;
 use crate::srtm::Srtm;
 
 pub struct Pbf<'a> {
     filename: &'a str,
+    /// Default speed limits apply for ways without one
+    country: Option<Country>,
     node_indices: HashMap<NodeId, usize>,
     number_nodes: usize,
+    turn_restrictions: Vec<TurnRestriction>,
+    /// Nodes of all ways which are part of a turn restriction
+    restricted_ways: HashMap<WayId, Vec<NodeId>>,
+    /// Node before the source and after the target of every edge along its way
+    way_neighbors: Vec<(Option<usize>, Option<usize>)>,
+    names: Names,
 }
 
 impl<'a> Pbf<'a> {
-    pub fn new(filename: &'a str) -> Self {
+    pub fn new(filename: &'a str, country: Option<Country>) -> Self {
         Self {
             filename,
+            country,
             node_indices: HashMap::new(),
             number_nodes: 0,
+            turn_restrictions: Vec::new(),
+            restricted_ways: HashMap::new(),
+            way_neighbors: Vec::new(),
+            names: Names::new(),
         }
     }
 
     pub fn read(&mut self) -> Graph {
+        debug!("Parsing turn restrictions...");
+        self.parse_relations();
         debug!("Parsing edges...");
-        let edges = self.parse_ways();
+        let mut edges = self.parse_ways();
         debug!("Parsed {} edges", edges.len());
+        let restrictions = self.resolve_restrictions();
+        debug!("Parsed {} turn restrictions", restrictions.len());
         debug!("Parsing nodes...");
         let nodes = self.parse_nodes();
         debug!("Parsed {} nodes", nodes.capacity());
+        self.set_curvature(&nodes, &mut edges);
         debug!("Creating graph...");
-        self.create_graph(nodes, edges)
+        let names = std::mem::take(&mut self.names);
+        create_graph(nodes, edges, restrictions, names)
+    }
+
+    fn parse_relations(&mut self) {
+        let mut pbf = read_pbf(self.filename);
+
+        for object in pbf.par_iter() {
+            if let OsmObj::Relation(relation) = object.unwrap() {
+                if let Some(restriction) = TurnRestriction::from(&relation) {
+                    for id in restriction.ways() {
+                        self.restricted_ways.insert(*id, Vec::new());
+                    }
+                    self.turn_restrictions.push(restriction);
+                }
+            }
+        }
     }
 
     fn parse_ways(&mut self) -> Vec<Edge> {
@@ -44,36 +82,97 @@ impl<'a> Pbf<'a> {
 
         for object in pbf.par_iter() {
             if let OsmObj::Way(way) = object.unwrap() {
-                let meta = if let Ok(meta) = edge::Meta::new(&way) {
+                let mut meta = if let Ok(meta) = edge::Meta::new(&way, self.country) {
                     meta
                 } else {
                     continue;
                 };
-                let is_oneway = is_oneway(&way);
+                meta.name = way.tags.get("name").map(|n| self.names.insert(n));
+                meta.reference = way.tags.get("ref").map(|r| self.names.insert(r));
+                let oneway = Oneway::from(&way, meta.highway);
+                let forward = meta.access.along(&oneway, true);
+                let backward = meta.access.along(&oneway, false);
+                let backward_speed = maxspeed::max_speed(&way, meta.highway, self.country, false);
+                if let Some(nodes) = self.restricted_ways.get_mut(&way.id) {
+                    nodes.clone_from(&way.nodes);
+                }
 
-                self.insert_node_id(*way.nodes.get(0).unwrap());
+                for id in &way.nodes {
+                    self.insert_node_id(*id);
+                }
                 for i in 1..way.nodes.len() {
                     let source_id = *way.nodes.get(i - 1).unwrap();
                     let source_index = *self.node_indices.get(&source_id).unwrap();
                     let target_id = *way.nodes.get(i).unwrap();
-                    self.insert_node_id(target_id);
                     let target_index = *self.node_indices.get(&target_id).unwrap();
 
-                    let edge = Edge::new(source_index, target_index, meta.clone());
-                    if !is_oneway {
-                        let mut reverse = edge.clone();
-                        reverse.source_index = target_index;
-                        reverse.target_index = source_index;
+                    let before = self.neighbor(&way, i.checked_sub(2));
+                    let after = self.neighbor(&way, Some(i + 1));
+
+                    // every direction only keeps the modes which may travel it
+                    if !backward.is_empty() {
+                        let mut reverse = Edge::new(target_index, source_index, meta.clone());
+                        reverse.meta.access = backward;
+                        reverse.meta.max_speed = backward_speed;
                         edges.push(reverse);
+                        self.way_neighbors.push((after, before));
+                    }
+                    if !forward.is_empty() {
+                        let mut edge = Edge::new(source_index, target_index, meta.clone());
+                        edge.meta.access = forward;
+                        edges.push(edge);
+                        self.way_neighbors.push((before, after));
                     }
-                    edges.push(edge);
                 }
             }
         }
-        edges.sort();
         edges
     }
 
+    fn neighbor(&self, way: &Way, i: Option<usize>) -> Option<usize> {
+        let id = way.nodes.get(i?)?;
+        self.node_indices.get(id).copied()
+    }
+
+    /// Scores how curvy the way is at both ends of each edge and finds the tightest curve
+    fn set_curvature(&mut self, nodes: &[Node], edges: &mut [Edge]) {
+        let score = |c1: Option<usize>, c2: usize, c3: Option<usize>| match (c1, c3) {
+            (Some(c1), Some(c3)) => Radius::gamma(
+                &nodes[c1].coordinates,
+                &nodes[c2].coordinates,
+                &nodes[c3].coordinates,
+            ).score(),
+            _ => 0.0
+        };
+        let radius = |c1: Option<usize>, c2: usize, c3: Option<usize>| Radius::meters(
+            &nodes[c1?].coordinates,
+            &nodes[c2].coordinates,
+            &nodes[c3?].coordinates,
+        );
+        for (edge, (before, after)) in edges.iter_mut().zip(self.way_neighbors.drain(..)) {
+            let source = score(before, edge.source_index, Some(edge.target_index));
+            let target = score(Some(edge.source_index), edge.target_index, after);
+            edge.meta.curvature = (source + target).round() as u8;
+            let radii = [
+                radius(before, edge.source_index, Some(edge.target_index)),
+                radius(Some(edge.source_index), edge.target_index, after),
+            ];
+            edge.meta.radius = radii.iter()
+                .flatten()
+                .map(|r| r.round().min(f32::from(u16::MAX)) as u16)
+                .min();
+        }
+    }
+
+    fn resolve_restrictions(&mut self) -> Vec<Restriction> {
+        let restrictions = self.turn_restrictions.iter()
+            .flat_map(|r| r.resolve(&self.restricted_ways, &self.node_indices))
+            .collect();
+        self.turn_restrictions.clear();
+        self.restricted_ways.clear();
+        restrictions
+    }
+
     fn parse_nodes(&mut self) -> Vec<Node> {
         let mut pbf = read_pbf(self.filename);
         let mut nodes = Vec::with_capacity(self.node_indices.len());
@@ -92,7 +191,7 @@ impl<'a> Pbf<'a> {
                     let meta = node::Meta::new(elevation);
 
                     let node = Node::new(id.0, coordinates, meta);
-                    let _ = mem::replace(&mut nodes[index], Some(node));
+                    nodes[index] = Some(node);
                 }
             }
         }
@@ -101,26 +200,6 @@ impl<'a> Pbf<'a> {
             .collect()
     }
 
-    fn create_graph(&self, nodes: Vec<Node>, mut edges: Vec<Edge>) -> Graph {
-        let offsets_len = self.number_nodes + 1;
-        let mut offsets = vec![0; offsets_len];
-
-        for edge in &mut edges {
-            let source = &nodes[edge.source_index];
-            let target = &nodes[edge.target_index];
-            edge.distance = Some(source.coordinates
-                .distance(&target.coordinates).round() as u32);
-            edge.meta.grade = grade(source, target, edge.distance());
-
-            offsets[edge.source_index + 1] += 1;
-        }
-
-        for i in 1..offsets.len() {
-            offsets[i] += offsets[i - 1]
-        }
-        Graph::new(nodes, offsets, edges)
-    }
-
     fn insert_node_id(&mut self, id: NodeId) {
         if self.node_indices.contains_key(&id) {
             return;
@@ -130,20 +209,50 @@ impl<'a> Pbf<'a> {
     }
 }
 
+pub fn create_graph(nodes: Vec<Node>, mut edges: Vec<Edge>,
+                    restrictions: Vec<Restriction>, names: Names) -> Graph {
+    edges.sort();
+    let mut offsets = vec![0; nodes.len() + 1];
+
+    for edge in &mut edges {
+        let source = &nodes[edge.source_index];
+        let target = &nodes[edge.target_index];
+        // rounded up so no path is shorter than the beeline of the distance heuristic
+        edge.distance = Some(source.coordinates
+            .distance(&target.coordinates).ceil() as u32);
+        edge.meta.grade = grade(source, target, edge.distance());
+
+        offsets[edge.source_index + 1] += 1;
+    }
+
+    for i in 1..offsets.len() {
+        offsets[i] += offsets[i - 1]
+    }
+    // more than the in and outgoing edge, same as counted for routes
+    for edge in &mut edges {
+        edge.meta.intersection = offsets[edge.target_index + 1] - offsets[edge.target_index] > 2;
+    }
+    Graph::new(nodes, offsets, edges, restrictions, names)
+}
+
 fn read_pbf(filename: &str) -> OsmPbfReader<File> {
     let path = Path::new(filename);
-    let file = File::open(&path).unwrap();
+    let file = File::open(path).unwrap();
     OsmPbfReader::new(file)
 }
 
-fn grade(source: &Node, target: &Node, distance: u32) -> Option<u8> {
+/// In tenths of a percent, positive if the edge goes uphill
+fn grade(source: &Node, target: &Node, distance: u32) -> Option<i16> {
     if let Some(source_e) = source.meta.elevation {
         if let Some(target_e) = target.meta.elevation {
-            let rise = (source_e as f32 - target_e as f32).abs();
+            if distance == 0 {
+                return Some(0);
+            }
+            let rise = target_e - source_e;
             let run = distance as f32;
-            let grade = (rise / run) * 100.0;
+            let grade = (rise / run) * 1000.0;
 
-            return Some(grade.round() as u8);
+            return Some(grade.round().clamp(-1000.0, 1000.0) as i16);
         }
     }
     None
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use osmpbfreader::{NodeId, OsmId, Relation, WayId};
+use serde::{Deserialize, Serialize};
+
+use crate::graph::restriction::Restriction;
+use crate::graph::router::options::Transport;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Kind {
+    /// e.g. no_left_turn, the turn onto the to way is forbidden
+    No,
+    /// e.g. only_straight_on, all turns except onto the to way are forbidden
+    Only,
+}
+
+/// Turn restriction relation, only restrictions with a via node are supported
+#[derive(Debug, Eq, PartialEq)]
+pub struct TurnRestriction {
+    pub from: Vec<WayId>,
+    pub via: NodeId,
+    pub to: Vec<WayId>,
+    pub kind: Kind,
+    pub transport: Transport,
+}
+
+impl TurnRestriction {
+    pub fn from(relation: &Relation) -> Option<Self> {
+        if !relation.tags.contains("type", "restriction") {
+            return None;
+        }
+        let (kind, transport) = kind_and_transport(relation)?;
+
+        let mut from = Vec::new();
+        let mut via = None;
+        let mut to = Vec::new();
+        for member in &relation.refs {
+            match (member.role.as_str(), member.member) {
+                ("from", OsmId::Way(id)) => from.push(id),
+                ("to", OsmId::Way(id)) => to.push(id),
+                ("via", OsmId::Node(id)) => via = Some(id),
+                // via ways are not supported
+                ("via", _) => return None,
+                _ => ()
+            }
+        }
+        if from.is_empty() || to.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            from,
+            via: via?,
+            to,
+            kind,
+            transport,
+        })
+    }
+
+    pub fn ways(&self) -> impl Iterator<Item=&WayId> {
+        self.from.iter().chain(self.to.iter())
+    }
+
+    /// Translates the ways into the nodes right before and after the via node
+    pub fn resolve(&self, ways: &HashMap<WayId, Vec<NodeId>>,
+                   node_indices: &HashMap<NodeId, usize>) -> Vec<Restriction> {
+        let via_index = if let Some(index) = node_indices.get(&self.via) { *index } else {
+            return Vec::new();
+        };
+        let from = self.approaches(ways, node_indices);
+        let to = self.neighbors(&self.to, ways, node_indices);
+
+        let mut restrictions = Vec::with_capacity(from.len() * to.len());
+        for from_index in &from {
+            for to_index in &to {
+                restrictions.push(Restriction {
+                    from_index: *from_index,
+                    via_index,
+                    to_index: *to_index,
+                    kind: self.kind,
+                    transport: self.transport,
+                });
+            }
+        }
+        restrictions
+    }
+
+    /// Nodes from which the via node is approached, i.e. the second to last nodes of the from ways.
+    /// From ways have to start or end at the via node, otherwise the direction is unclear.
+    fn approaches(&self, ways: &HashMap<WayId, Vec<NodeId>>, node_indices: &HashMap<NodeId, usize>) -> Vec<usize> {
+        let mut approaches = Vec::new();
+        for nodes in self.from.iter().filter_map(|id| ways.get(id)).filter(|nodes| nodes.len() > 1) {
+            if nodes[nodes.len() - 1] == self.via {
+                approaches.push(nodes[nodes.len() - 2]);
+            }
+            if nodes[0] == self.via {
+                approaches.push(nodes[1]);
+            }
+        }
+        approaches.iter()
+            .filter_map(|n| node_indices.get(n))
+            .copied()
+            .collect()
+    }
+
+    fn neighbors(&self, way_ids: &[WayId], ways: &HashMap<WayId, Vec<NodeId>>,
+                 node_indices: &HashMap<NodeId, usize>) -> Vec<usize> {
+        let mut neighbors = Vec::new();
+        for nodes in way_ids.iter().filter_map(|id| ways.get(id)) {
+            for (i, _) in nodes.iter().enumerate().filter(|(_, n)| **n == self.via) {
+                if i > 0 {
+                    neighbors.push(nodes[i - 1]);
+                }
+                if i + 1 < nodes.len() {
+                    neighbors.push(nodes[i + 1]);
+                }
+            }
+        }
+        neighbors.iter()
+            .filter_map(|n| node_indices.get(n))
+            .copied()
+            .collect()
+    }
+}
+
+fn kind_and_transport(relation: &Relation) -> Option<(Kind, Transport)> {
+    let (value, transport) = if let Some(value) = relation.tags.get("restriction") {
+        // applies to all vehicles, pedestrians are never restricted
+        let except = relation.tags.get("except").map_or("", |e| e.as_str());
+        let transport = match (except.contains("motorcar"), except.contains("bicycle")) {
+            (false, false) => Transport::CarBike,
+            (false, true) => Transport::Car,
+            (true, false) => Transport::Bike,
+            (true, true) => return None,
+        };
+        (value, transport)
+    } else if let Some(value) = relation.tags.get("restriction:motorcar") {
+        (value, Transport::Car)
+    } else if let Some(value) = relation.tags.get("restriction:bicycle") {
+        (value, Transport::Bike)
+    } else {
+        return None;
+    };
+
+    let kind = if value.starts_with("no_") {
+        Kind::No
+    } else if value.starts_with("only_") {
+        Kind::Only
+    } else {
+        return None;
+    };
+    Some((kind, transport))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use osmpbfreader::{NodeId, OsmId, Ref, Relation, RelationId, WayId};
+
+    use crate::graph::restriction::Restriction;
+    use crate::graph::router::options::Transport;
+    use crate::osm::restriction::{Kind, TurnRestriction};
+
+    fn relation(tags: &[(&str, &str)], refs: &[(&str, OsmId)]) -> Relation {
+        Relation {
+            id: RelationId(1),
+            tags: tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            refs: refs.iter()
+                .map(|(role, member)| Ref { member: *member, role: role.to_string() })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let refs = [
+            ("from", OsmId::Way(WayId(1))),
+            ("via", OsmId::Node(NodeId(10))),
+            ("to", OsmId::Way(WayId(2))),
+        ];
+        let no_left_turn = relation(
+            &[("type", "restriction"), ("restriction", "no_left_turn"), ("except", "bicycle")],
+            &refs,
+        );
+        assert_eq!(TurnRestriction::from(&no_left_turn), Some(TurnRestriction {
+            from: vec![WayId(1)],
+            via: NodeId(10),
+            to: vec![WayId(2)],
+            kind: Kind::No,
+            transport: Transport::Car,
+        }));
+
+        let only_straight_on = relation(
+            &[("type", "restriction"), ("restriction", "only_straight_on")],
+            &refs,
+        );
+        let restriction = TurnRestriction::from(&only_straight_on).unwrap();
+        assert_eq!(restriction.kind, Kind::Only);
+        assert_eq!(restriction.transport, Transport::CarBike);
+
+        let via_way = relation(
+            &[("type", "restriction"), ("restriction", "no_u_turn")],
+            &[refs[0], ("via", OsmId::Way(WayId(3))), refs[2]],
+        );
+        assert_eq!(TurnRestriction::from(&via_way), None);
+        let no_restriction = relation(&[("type", "route")], &refs);
+        assert_eq!(TurnRestriction::from(&no_restriction), None);
+    }
+
+    #[test]
+    fn resolve() {
+        let restriction = TurnRestriction {
+            from: vec![WayId(1)],
+            via: NodeId(10),
+            to: vec![WayId(2)],
+            kind: Kind::No,
+            transport: Transport::Car,
+        };
+        let mut ways = HashMap::new();
+        // from way ends at the via node, to way passes through it
+        ways.insert(WayId(1), vec![NodeId(8), NodeId(9), NodeId(10)]);
+        ways.insert(WayId(2), vec![NodeId(11), NodeId(10), NodeId(12)]);
+        let node_indices: HashMap<NodeId, usize> = (8..=12)
+            .map(|id| (NodeId(id), id as usize - 8))
+            .collect();
+
+        let restrictions = restriction.resolve(&ways, &node_indices);
+        assert_eq!(restrictions, vec![
+            Restriction { from_index: 1, via_index: 2, to_index: 3, kind: Kind::No, transport: Transport::Car },
+            Restriction { from_index: 1, via_index: 2, to_index: 4, kind: Kind::No, transport: Transport::Car },
+        ]);
+
+        // only the segment ending at the via node is restricted, not the one leaving it
+        ways.insert(WayId(1), vec![NodeId(10), NodeId(9)]);
+        assert_eq!(restriction.resolve(&ways, &node_indices)[0].from_index, 1);
+        ways.insert(WayId(1), vec![NodeId(8), NodeId(10), NodeId(9)]);
+        assert!(restriction.resolve(&ways, &node_indices).is_empty());
+    }
+}
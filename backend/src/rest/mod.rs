@@ -8,20 +8,24 @@ use actix_cors::Cors;
 use actix_files::{Files, NamedFile};
 use actix_web::{App, HttpResponse, HttpServer, ResponseError};
 use actix_web::get;
+use actix_web::http::StatusCode;
 use actix_web::middleware::Logger;
 use actix_web::post;
 use actix_web::Result;
-use actix_web::web::{Data, Json};
+use actix_web::web::{Data, Json, Query};
 use futures::executor::block_on;
 use log::debug;
 use serde::{Deserialize, Serialize};
 use serde::export::Formatter;
 
+use crate::geojson::{self, Polygons};
+use crate::gpx;
 use crate::graph::Graph;
-use crate::graph::router::options::{Params, Routing};
+use crate::graph::router::options::{Avoid, Params, Routing, SpeedModel, TurnCosts};
 use crate::graph::router::options::Transport;
 use crate::graph::router::route::Route;
-use crate::graph::router::Router;
+use crate::graph::router;
+use crate::graph::router::area::Area;
 use crate::osm::Coordinates;
 
 const ADDRESS: &str = "localhost:8000";
@@ -40,6 +44,12 @@ pub fn init(graph: Graph) {
                 .show_files_listing()
                 .use_last_modified(true))
             .service(shortest_path)
+            .service(round_trip)
+            .service(isochrone)
+            .service(matrix)
+            .service(export_gpx)
+            .service(import_gpx)
+            .service(match_gpx)
 
             .wrap(Logger::default())
             .wrap(Cors::new()
@@ -60,52 +70,323 @@ async fn shortest_path(state: Data<Graph>, request: Json<Request>) -> Result<Htt
     debug!("Calculating path...");
     let now = Instant::now();
     let graph = state.get_ref();
-    let params = Params::new(
-        Transport::from_str(&request.transport).unwrap(),
-        Routing::from_str(&request.routing).unwrap(),
-        request.avoid_unpaved,
-    );
-
-    let mut route: Option<Route> = None;
-    for i in 0..request.stops.len() - 1 {
-        let start = &request.stops[i];
-        let goal = &request.stops[i + 1];
-        let mut router = Router::new(graph, params.clone());
-
-        match router.shortest_path(start, goal) {
-            Ok(part) => {
-                if let Some(rt) = route.as_mut() {
-                    rt.merge(part);
-                } else {
-                    route = Some(part);
-                }
-            }
+    let params = request.options.params()?;
+
+    let (stops, order) = ordered_stops(graph, &params, &request)?;
+    let route = route(graph, &params, &stops, now)?;
+    let alternatives = if stops.len() == 2 && request.alternatives > 0 {
+        router::alternatives::alternatives(graph, &params, &stops[0], &stops[1],
+                                           &route, request.alternatives, request.max_overlap)
+    } else {
+        // alternatives of routes with intermediate stops are not supported
+        Vec::new()
+    };
+
+    debug!("Calculated path in {}ms", now.elapsed().as_millis());
+    Ok(HttpResponse::Ok().json(&Response { route, alternatives, order }))
+}
+
+#[post("/gpx/export")]
+async fn export_gpx(state: Data<Graph>, request: Json<Request>) -> Result<HttpResponse, Error> {
+    debug!("Exporting path as GPX...");
+    let now = Instant::now();
+    let graph = state.get_ref();
+    let params = request.options.params()?;
+
+    let (stops, _) = ordered_stops(graph, &params, &request)?;
+    let route = route(graph, &params, &stops, now)?;
+    debug!("Exported path in {}ms", now.elapsed().as_millis());
+    Ok(HttpResponse::Ok()
+        .content_type("application/gpx+xml")
+        .body(gpx::export(&route, &stops)))
+}
+
+/// Routes through the waypoints of the GPX file in the body
+#[post("/gpx/import")]
+async fn import_gpx(state: Data<Graph>, options: Query<Options>, body: String) -> Result<HttpResponse, Error> {
+    debug!("Importing GPX...");
+    let now = Instant::now();
+    let stops = gpx::import(&body).map_err(|err| Error::BadRequest(err.to_string()))?;
+
+    let route = route(state.get_ref(), &options.params()?, &stops, now)?;
+    debug!("Imported GPX in {}ms", now.elapsed().as_millis());
+    Ok(HttpResponse::Ok().json(&Response { route, alternatives: Vec::new(), order: None }))
+}
+
+/// Matches the recorded track of the GPX file in the body onto the map
+#[post("/gpx/match")]
+async fn match_gpx(state: Data<Graph>, options: Query<Options>, body: String) -> Result<HttpResponse, Error> {
+    debug!("Matching GPX track...");
+    let now = Instant::now();
+    let trace = gpx::track(&body).map_err(|err| Error::BadRequest(err.to_string()))?;
+
+    let route = router::matching::match_trace(state.get_ref(), &options.params()?, &trace)
+        .map_err(|err| Error::BadRequest(err.to_string()))?;
+    debug!("Matched {} points in {}ms", trace.len(), now.elapsed().as_millis());
+    Ok(HttpResponse::Ok().json(&Response { route, alternatives: Vec::new(), order: None }))
+}
+
+/// The stops of the request, reordered together with their new order if requested
+fn ordered_stops(graph: &Graph, params: &Params, request: &Request)
+                 -> Result<(Vec<Coordinates>, Option<Vec<usize>>), Error> {
+    if !request.optimize {
+        return Ok((request.stops.clone(), None));
+    }
+    let order = router::tsp::optimize(graph, params, &request.stops, request.keep_first, request.keep_last)
+        .map_err(|err| Error::Internal(err.to_string()))?;
+    let stops = order.iter().map(|i| request.stops[*i].clone()).collect();
+    Ok((stops, Some(order)))
+}
+
+/// Route through all stops in the given order
+fn route(graph: &Graph, params: &Params, stops: &[Coordinates], now: Instant) -> Result<Route, Error> {
+    let mut parts = Vec::with_capacity(stops.len().saturating_sub(1));
+    for i in 0..stops.len().saturating_sub(1) {
+        let start = &stops[i];
+        let goal = &stops[i + 1];
+        match router::shortest_path(graph, params, start, goal) {
+            Ok(part) => parts.push(part),
             Err(err) => {
                 debug!("No path found, calculation took {}ms", now.elapsed().as_millis());
-                return Err(Error(err.to_string()));
+                return Err(Error::Internal(err.to_string()));
             }
         }
     }
+    Route::concat(parts).ok_or_else(|| Error::BadRequest("At least two stops are required".to_string()))
+}
 
-    debug!("Calculated path in {}ms", now.elapsed().as_millis());
+#[post("/round-trip")]
+async fn round_trip(state: Data<Graph>, request: Json<RoundTripRequest>) -> Result<HttpResponse, Error> {
+    debug!("Calculating round trip...");
+    let now = Instant::now();
+    let graph = state.get_ref();
+    let mut params = request.options.params()?;
+    params.hilliness = request.hilliness;
+
+    let route = router::round_trip::round_trip(graph, &params, &request.start, request.distance)
+        .map_err(|err| {
+            debug!("No round trip found, calculation took {}ms", now.elapsed().as_millis());
+            Error::Internal(err.to_string())
+        })?;
+    debug!("Calculated round trip in {}ms", now.elapsed().as_millis());
     Ok(HttpResponse::Ok().json(&route))
 }
 
+/// Areas reachable within the budgets as GeoJSON
+#[post("/isochrone")]
+async fn isochrone(state: Data<Graph>, request: Json<IsochroneRequest>) -> Result<HttpResponse, Error> {
+    debug!("Calculating isochrones...");
+    let now = Instant::now();
+    let params = request.options.params()?;
+
+    let contours = router::isochrone::isochrones(state.get_ref(), &params, &request.start, &request.budgets)
+        .map_err(|err| Error::Internal(err.to_string()))?;
+    debug!("Calculated {} isochrones in {}ms", contours.len(), now.elapsed().as_millis());
+    Ok(HttpResponse::Ok()
+        .content_type("application/geo+json")
+        .json(geojson::isochrones(&contours)))
+}
+
+/// Travel times and distances between all sources and targets
+#[post("/matrix")]
+async fn matrix(state: Data<Graph>, request: Json<MatrixRequest>) -> Result<HttpResponse, Error> {
+    debug!("Calculating matrix...");
+    let now = Instant::now();
+    let params = request.options.params()?;
+    let targets = request.targets.as_ref().unwrap_or(&request.sources);
+
+    let matrix = router::matrix::matrix(state.get_ref(), &params, &request.sources, targets)
+        .map_err(|err| Error::Internal(err.to_string()))?;
+    debug!("Calculated {}x{} matrix in {}ms", request.sources.len(), targets.len(), now.elapsed().as_millis());
+    Ok(HttpResponse::Ok().json(&matrix))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Request {
     stops: Vec<Coordinates>,
+    /// Maximum number of alternative routes, only for requests without intermediate stops
+    #[serde(default)]
+    alternatives: usize,
+    /// Maximum share of an alternative's distance which may overlap with other routes
+    #[serde(default = "default_max_overlap")]
+    max_overlap: f32,
+    /// Reorders the stops to minimize the total travel time or distance
+    #[serde(default)]
+    optimize: bool,
+    /// Whether the first and last stop keep their position when optimizing the order
+    #[serde(default = "default_keep")]
+    keep_first: bool,
+    #[serde(default = "default_keep")]
+    keep_last: bool,
+    #[serde(flatten)]
+    options: Options,
+}
+
+fn default_max_overlap() -> f32 {
+    0.5
+}
+
+fn default_keep() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    #[serde(flatten)]
+    route: Route,
+    alternatives: Vec<Route>,
+    /// Indices of the requested stops in the order they are visited, if it was optimized
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order: Option<Vec<usize>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RoundTripRequest {
+    start: Coordinates,
+    /// Desired length of the loop in meters
+    distance: u32,
+    /// Preference for climbs and descents, 0 ignores the grade
+    #[serde(default)]
+    hilliness: f32,
+    #[serde(flatten)]
+    options: Options,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IsochroneRequest {
+    start: Coordinates,
+    /// Contour levels in seconds for time routing and in meters otherwise
+    budgets: Vec<u32>,
+    #[serde(flatten)]
+    options: Options,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MatrixRequest {
+    sources: Vec<Coordinates>,
+    /// Same as the sources if missing
+    #[serde(default)]
+    targets: Option<Vec<Coordinates>>,
+    #[serde(flatten)]
+    options: Options,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Options {
     transport: String,
     routing: String,
     avoid_unpaved: bool,
+    /// Enables turn penalties, missing penalties are set to their defaults
+    #[serde(default)]
+    turn_costs: Option<TurnCosts>,
+    /// Detour accepted for curvy roads, only used for curvy routing
+    #[serde(default)]
+    curviness: Option<f32>,
+    /// Steepest climb in percent which is still allowed
+    #[serde(default)]
+    max_grade: Option<u8>,
+    /// Penalizes climbs, 0 ignores the grade
+    #[serde(default)]
+    grade_weight: f32,
+    /// Highway types like `motorway` or `ferry` and `toll` for toll roads
+    #[serde(default)]
+    avoid: Vec<String>,
+    /// Edges intersecting the areas are never used
+    #[serde(default)]
+    avoid_areas: Vec<AvoidArea>,
+    /// Speeds of bikes and pedestrians for time routing, constant if missing
+    #[serde(default)]
+    speed_model: SpeedModel,
+}
+
+/// Either a ring of coordinates or a GeoJSON polygon or multipolygon
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum AvoidArea {
+    Ring(Vec<Coordinates>),
+    GeoJson(Polygons),
+}
+
+impl Options {
+    fn params(&self) -> Result<Params, Error> {
+        let mut params = Params::new(
+            Transport::from_str(&self.transport).map_err(|err| Error::BadRequest(err.to_string()))?,
+            Routing::from_str(&self.routing).map_err(|err| Error::BadRequest(err.to_string()))?,
+            self.avoid_unpaved,
+        );
+        params.turn_costs = self.turn_costs;
+        if let Some(curviness) = self.curviness {
+            params.curviness = curviness;
+        }
+        params.max_grade = self.max_grade;
+        params.grade_weight = self.grade_weight;
+        params.avoid = self.avoid.iter()
+            .map(|avoid| Avoid::from_str(avoid).map_err(|err| Error::BadRequest(err.to_string())))
+            .collect::<Result<_, _>>()?;
+        params.avoid_areas = self.avoid_areas.iter()
+            .flat_map(|area| match area {
+                AvoidArea::Ring(ring) => vec![Area::new(vec![ring.clone()])],
+                AvoidArea::GeoJson(polygons) => polygons.areas(),
+            })
+            .collect();
+        params.speed_model = self.speed_model;
+        Ok(params)
+    }
 }
 
 #[derive(Debug)]
-struct Error(String);
+enum Error {
+    /// The request itself is invalid, e.g. because of unknown options
+    BadRequest(String),
+    Internal(String),
+}
 
-impl ResponseError for Error {}
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
-        f.write_str(&self.0)
+        match self {
+            Error::BadRequest(message) | Error::Internal(message) => f.write_str(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    use crate::graph::fixtures;
+    use crate::graph::router::options::{Params, Routing, Transport};
+    use crate::osm::Coordinates;
+    use crate::rest::{Options, route};
+
+    fn options(avoid: &str) -> Options {
+        let json = format!(r#"{{"transport": "car", "routing": "time", "avoid_unpaved": false, "avoid": [{}]}}"#, avoid);
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(options(r#""motorway", "toll""#).params().is_ok());
+        let err = options(r#""speed_bumps""#).params().unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn single_stop() {
+        let graph = fixtures::graph(&[(48.0, 9.0), (48.01, 9.0)], &[(0, 1, "residential")]);
+        let params = Params::new(Transport::Car, Routing::Time, false);
+        let err = route(&graph, &params, &[Coordinates::from(48.0, 9.0)], Instant::now()).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
     }
 }
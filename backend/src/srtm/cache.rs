@@ -71,7 +71,7 @@ fn download(filename: &str, url: &str) -> Vec<u8> {
     let content = reqwest::blocking::get(url).unwrap();
     let zip = zip(content);
     let bytes = unzip(zip, filename);
-    fs::write(&filename, &bytes).unwrap();
+    fs::write(filename, &bytes).unwrap();
     bytes
 }
 
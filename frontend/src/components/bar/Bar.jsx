@@ -26,6 +26,7 @@ const BIKE = 'bike';
 const WALK = 'walk';
 const TIME = 'time';
 const DISTANCE = 'distance';
+const CURVY = 'curvy';
 
 export default class Bar extends React.Component {
   constructor(props) {
@@ -48,7 +49,7 @@ export default class Bar extends React.Component {
         rtng = routing;
       } else if (newTransport === BIKE || newTransport === WALK) {
         disableTime = true;
-        rtng = DISTANCE;
+        rtng = routing === TIME ? DISTANCE : routing;
       }
       this.setState({
         transport: newTransport,
@@ -136,6 +137,12 @@ export default class Bar extends React.Component {
                     label="Distance"
                     labelPlacement="start"
                   />
+                  <FormControlLabel
+                    value={CURVY}
+                    control={<Radio color="primary" />}
+                    label="Curvy"
+                    labelPlacement="start"
+                  />
                 </div>
               </RadioGroup>
             </div>
@@ -36,7 +36,7 @@ export default class ElevationGraph extends React.Component {
 
         labels.push(`${node.coordinates.lat}, ${node.coordinates.lon}`);
         items.push(node.meta.elevation.toFixed(1));
-        backgroundColors.push(this.color(edge.meta.grade));
+        backgroundColors.push(this.color(edge.meta.grade / 10));
       }
       return { labels, items, backgroundColors };
     };
@@ -54,7 +54,7 @@ export default class ElevationGraph extends React.Component {
           .map((n) => n.meta.elevation);
         const avgElevation = (elevations.reduce((e1, e2) => e1 + e2, 0) / elevations.length);
         const grades = edges.slice(i, i + smoothing)
-          .map((e) => e.meta.grade);
+          .map((e) => e.meta.grade / 10);
         const avgGrade = Math.round(grades.reduce((e1, e2) => e1 + e2, 0) / grades.length);
 
         labels.push(`${nodes[i].coordinates.lat}, ${nodes[i].coordinates.lon}`);
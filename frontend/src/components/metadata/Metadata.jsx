@@ -25,6 +25,18 @@ export default class Metadata extends React.Component {
             {' '}
             {route && route.intersections}
           </div>
+          <div className={css.metadataContainer}>
+            <b>Ascent / Descent:</b>
+            {' '}
+            {route && `${Math.round(route.ascent)} m / ${Math.round(route.descent)} m`}
+          </div>
+          <div className={css.metadataContainer}>
+            <b>Climbs:</b>
+            {' '}
+            {route && route.climbs
+              .map((c) => `Cat. ${c.category} (${(c.distance / 1000).toFixed(1)} km, ${c.avg_grade.toFixed(1)}%)`)
+              .join(', ')}
+          </div>
 
           <div className={css.metadataContainer}>
             <ElevationGraph
@@ -15,6 +15,7 @@ const roadTypeColors = {
   Steps: '#fbff12',
   Path: '#ffa5ab',
   Cycleway: '#276321',
+  Ferry: '#00b4d8',
 };
 
 const roadSurfaceColors = {